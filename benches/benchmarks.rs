@@ -83,6 +83,23 @@ fn nearest_neighbor(c: &mut Criterion) {
     });
 }
 
+fn k_nearest_neighbors(c: &mut Criterion) {
+    const SIZE: usize = 100_000;
+    let mut points: Vec<_> = create_random_point_type_points(SIZE, SEED_1);
+    let query_points = create_random_points(100, SEED_2);
+
+    let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+    map.batch_insert(&mut points).unwrap();
+
+    c.bench_function("k_nearest_neigbors_10", move |b| {
+        b.iter(|| {
+            for query_point in &query_points {
+                map.k_nearest_neighbors(query_point, 10);
+            }
+        });
+    });
+}
+
 fn radius_range(c: &mut Criterion) {
     const SIZE: usize = 100_000;
     let mut points: Vec<_> = create_random_point_type_points(SIZE, SEED_1);
@@ -104,6 +121,31 @@ fn radius_range(c: &mut Criterion) {
     }
 }
 
+fn frozen(c: &mut Criterion) {
+    const SIZE: usize = 100_000;
+    let mut points: Vec<_> = create_random_point_type_points(SIZE, SEED_1);
+    let query_points = create_random_points(100, SEED_2);
+
+    let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+    map.batch_insert(&mut points).unwrap();
+    let frozen = map.freeze();
+
+    c.bench_function("frozen_radius_range_0.1", |b| {
+        b.iter(|| {
+            for query_point in &query_points {
+                frozen.radius_range(query_point, 0.1).unwrap();
+            }
+        });
+    })
+    .bench_function("frozen_k_nearest_neigbors_10", |b| {
+        b.iter(|| {
+            for query_point in &query_points {
+                frozen.k_nearest_neighbors(query_point, 10);
+            }
+        });
+    });
+}
+
 criterion_group!(
     benches,
     bulk_load_baseline,
@@ -111,6 +153,8 @@ criterion_group!(
     locate_unsuccessful,
     radius_range,
     nearest_neighbor,
+    k_nearest_neighbors,
+    frozen,
 );
 criterion_main!(benches);
 
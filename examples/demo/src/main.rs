@@ -15,8 +15,11 @@ use clap::{Arg, Command};
 use colored::*;
 use csv::ReaderBuilder;
 use lsph::{
-    geometry::Point,
-    map::LearnedHashMap,
+    geometry::{
+        distance::{Distance, Haversine},
+        Point,
+    },
+    map::{LearnedHashMap, Metric},
     models::LinearModel,
 };
 use rand::Rng;
@@ -47,7 +50,7 @@ struct PerformanceStats {
     total_points: usize,
     nearest_neighbor_times: Vec<Duration>,
     range_query_times: Vec<Duration>,
-    memory_usage_estimate: usize,
+    memory_usage: usize,
 }
 
 /// Main demo application
@@ -61,7 +64,11 @@ impl LSPHDemo {
     /// Create a new demo instance
     fn new() -> Self {
         Self {
-            spatial_map: LearnedHashMap::new(),
+            // Geographic mode, the radius and the distances of the queries are in metres
+            spatial_map: LearnedHashMap::builder()
+                .metric(Metric::Haversine)
+                .build()
+                .expect("valid geographic configuration"),
             points: Vec::new(),
             stats: PerformanceStats::default(),
         }
@@ -147,19 +154,22 @@ impl LSPHDemo {
             let point = Point::new(geo_point.latitude, geo_point.longitude);
 
             match self.spatial_map.insert(point) {
-                Some(_existing) => {
+                Ok(Some(_existing)) => {
                     // Point already existed, this is fine
                     successful_insertions += 1;
                 }
-                None => {
+                Ok(None) => {
                     // New point inserted successfully
                     successful_insertions += 1;
                 }
+                Err(err) => {
+                    eprintln!("Failed to insert point {:?}: {}", point, err);
+                }
             }
         }
 
         self.stats.index_building_time = start_time.elapsed();
-        self.stats.memory_usage_estimate = self.estimate_memory_usage();
+        self.stats.memory_usage = self.memory_usage();
 
         println!(
             "✅ Built spatial index in {:.2}ms",
@@ -170,21 +180,16 @@ impl LSPHDemo {
             successful_insertions.to_string().bright_green()
         );
         println!(
-            "💾 Estimated memory usage: {:.2} MB",
-            self.stats.memory_usage_estimate as f64 / 1_048_576.0
+            "💾 Memory usage: {:.2} MB",
+            self.stats.memory_usage as f64 / 1_048_576.0
         );
 
         Ok(())
     }
 
-    /// Estimate memory usage of the spatial map
-    fn estimate_memory_usage(&self) -> usize {
-        // Rough estimation based on point count and structure overhead
-        let point_size = std::mem::size_of::<Point<f64>>();
-        let base_overhead = 1024; // Base structure overhead
-        let per_point_overhead = 64; // Hash table and indexing overhead per point
-
-        base_overhead + (self.stats.total_points * (point_size + per_point_overhead))
+    /// Memory usage of the spatial map, the map itself and the heap it owns
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of_val(&self.spatial_map) + self.spatial_map.stats().heap_bytes
     }
 
     /// Perform nearest neighbor search demonstrations
@@ -215,12 +220,8 @@ impl LSPHDemo {
                     successful_queries += 1;
                     if i < 5 {
                         // Show details for first few queries
-                        let distance = self.calculate_distance(
-                            query_lat,
-                            query_lng,
-                            nearest.x(),
-                            nearest.y(),
-                        );
+                        let distance =
+                            Haversine::distance(&query_point, &[nearest.x(), nearest.y()]);
                         println!(
                             "🔍 Query {}: ({:.5}, {:.5}) → Nearest: ({:.5}, {:.5}) | Distance: {:.2}m | Time: {:.2}μs",
                             (i + 1).to_string().cyan(),
@@ -276,14 +277,10 @@ impl LSPHDemo {
         );
 
         let mut rng = rand::rng();
-        let radii = [0.001, 0.005, 0.01, 0.02]; // Different search radii in degrees
+        let radii = [100.0, 500.0, 1_000.0, 2_000.0]; // Different search radii in metres
 
         for &radius in &radii {
-            println!(
-                "\n🔍 Testing radius: {:.3}° (~{:.0}m)",
-                radius,
-                radius * 111_000.0 // Rough conversion to meters
-            );
+            println!("\n🔍 Testing radius: {:.0}m", radius);
 
             let mut total_results = 0;
             let mut query_times = Vec::new();
@@ -340,16 +337,6 @@ impl LSPHDemo {
         }
     }
 
-    /// Calculate approximate distance between two geographic points
-    fn calculate_distance(&self, lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
-        let dlat = (lat2 - lat1).to_radians();
-        let dlng = (lng2 - lng1).to_radians();
-        let a = (dlat / 2.0).sin().powi(2)
-            + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlng / 2.0).sin().powi(2);
-        let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-        6371000.0 * c // Earth radius in meters
-    }
-
     /// Display comprehensive performance summary
     fn display_performance_summary(&self) {
         println!(
@@ -418,13 +405,26 @@ impl LSPHDemo {
 
         println!("\n💾 Memory Usage:");
         println!(
-            "   Estimated total: {:.2} MB",
-            self.stats.memory_usage_estimate as f64 / 1_048_576.0
+            "   Total: {:.2} MB",
+            self.stats.memory_usage as f64 / 1_048_576.0
         );
         println!(
             "   Per point: {:.1} bytes",
-            self.stats.memory_usage_estimate as f64 / self.stats.total_points as f64
+            self.stats.memory_usage as f64 / self.stats.total_points as f64
+        );
+
+        let map_stats = self.spatial_map.stats();
+        println!("\n🪣 Buckets:");
+        println!(
+            "   Buckets: {} ({:.1}% empty)",
+            map_stats.n_buckets,
+            map_stats.empty_bucket_ratio * 100.0
+        );
+        println!(
+            "   Chain length: max {}, mean {:.2}",
+            map_stats.max_bucket_len, map_stats.mean_bucket_len
         );
+        println!("   Model RMSE: {:.2}", map_stats.rmse);
     }
 
     /// Run interactive mode
@@ -463,7 +463,7 @@ impl LSPHDemo {
                             match self.spatial_map.nearest_neighbor(&query_point) {
                                 Some(nearest) => {
                                     let query_time = start_time.elapsed();
-                                    let distance = self.calculate_distance(lat, lng, nearest.x(), nearest.y());
+                                    let distance = Haversine::distance(&query_point, &[nearest.x(), nearest.y()]);
                                     
                                     println!(
                                         "✅ Nearest point: ({:.5}, {:.5})",
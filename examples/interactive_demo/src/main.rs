@@ -73,8 +73,10 @@ impl LSPHDemo {
     fn add_point(&mut self, x: f64, y: f64, value: f64) {
         let point = Point::new(x, y);
         
-        // Add to LSPH
-        let _existing = self.spatial_map.insert(point);
+        // Add to LSPH, skip points the map rejects
+        if self.spatial_map.insert(point).is_err() {
+            return;
+        }
         
         // Add to visualization
         let index = self.points.len();
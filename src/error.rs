@@ -1,12 +1,50 @@
-/// The kinds of errors that can occur when calculating a linear regression.
+use core::fmt;
+
+/// The kinds of errors that can occur when training a model or building the map.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Error {
     /// The slope is too steep to represent, approaching infinity.
     SteepSlope,
 
-    /// Different input lenses  
+    /// Different input lenses
     DiffLen,
 
     /// Input was empty
     EmptyVal,
+
+    /// A coordinate is NaN or infinite
+    NonFiniteCoordinate,
+
+    /// All the points share the same coordinate along every axis, so no axis can be learned
+    DegenerateAxis,
+
+    /// The fitted model is not monotonic, so the buckets would not be ordered
+    NotMonotonic,
+
+    /// The table capacity required by the hash cannot be allocated
+    CapacityOverflow,
+
+    /// A value of the map configuration is out of range
+    InvalidConfig,
+
+    /// A coordinate along the periodic axis of the map is outside of its domain
+    OutOfDomain,
 }
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SteepSlope => write!(f, "slope is too steep to represent"),
+            Error::DiffLen => write!(f, "inputs differ in length"),
+            Error::EmptyVal => write!(f, "input is empty"),
+            Error::NonFiniteCoordinate => write!(f, "coordinate is NaN or infinite"),
+            Error::DegenerateAxis => write!(f, "points share the same coordinate on every axis"),
+            Error::NotMonotonic => write!(f, "model is not monotonic"),
+            Error::CapacityOverflow => write!(f, "table capacity overflow"),
+            Error::InvalidConfig => write!(f, "map configuration is out of range"),
+            Error::OutOfDomain => write!(f, "coordinate is outside of the periodic domain"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
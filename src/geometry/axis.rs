@@ -5,3 +5,20 @@ pub enum Axis {
     Y,
     Z,
 }
+
+impl Axis {
+    /// Returns the index of the dimension of the axis
+    pub fn index(&self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+impl From<Axis> for usize {
+    fn from(axis: Axis) -> usize {
+        axis.index()
+    }
+}
@@ -5,10 +5,26 @@ use num_traits::float::Float;
 /// Distance trait for measuring the distance between two points
 pub trait Distance {
     type F;
-    /// Distance between two points in tuple format
-    fn distance(a: &[Self::F; 2], b: &[Self::F; 2]) -> Self::F;
+    /// Distance between two points in array format
+    fn distance<const D: usize>(a: &[Self::F; D], b: &[Self::F; D]) -> Self::F;
     /// Distance between two points in points format
-    fn distance_point(a: &Point<Self::F>, b: &Point<Self::F>) -> Self::F;
+    fn distance_point<const D: usize>(a: &Point<Self::F, D>, b: &Point<Self::F, D>) -> Self::F {
+        Self::distance(&a.coords, &b.coords)
+    }
+    /// Lower bound of the distance between the query point and any point whose coordinate
+    /// along the axis is `key`, which the queries use to prune buckets. It must not decrease as
+    /// `key` moves away from the coordinate of the query point.
+    ///
+    /// The default is the distance to the query point moved to `key` along the axis, which is a
+    /// lower bound for every metric that does not decrease as a coordinate difference grows.
+    fn lower_bound<const D: usize>(query: &[Self::F; D], axis: usize, key: Self::F) -> Self::F
+    where
+        Self::F: Float,
+    {
+        let mut p = *query;
+        p[axis] = key;
+        Self::distance(query, &p)
+    }
 }
 
 /// Euclidean Distance
@@ -21,12 +37,37 @@ where
     F: Float,
 {
     type F = F;
-    fn distance(a: &[F; 2], b: &[F; 2]) -> F {
-        F::sqrt((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2))
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        a.iter()
+            .zip(b.iter())
+            .fold(F::zero(), |sum, (&a, &b)| sum + (a - b).powi(2))
+            .sqrt()
     }
 
-    fn distance_point(a: &Point<Self::F>, b: &Point<Self::F>) -> Self::F {
-        Self::distance(&[a.x, a.y], &[b.x, b.y])
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        (query[axis] - key).abs()
+    }
+}
+
+/// Squared Euclidean Distance, which orders the points as the Euclidean distance without the
+/// square root. A radius is the square of the Euclidean radius.
+pub struct SquaredEuclidean<F: Float> {
+    _marker: PhantomData<F>,
+}
+
+impl<F> Distance for SquaredEuclidean<F>
+where
+    F: Float,
+{
+    type F = F;
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        a.iter()
+            .zip(b.iter())
+            .fold(F::zero(), |sum, (&a, &b)| sum + (a - b).powi(2))
+    }
+
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        (query[axis] - key).powi(2)
     }
 }
 
@@ -40,12 +81,118 @@ where
     F: Float,
 {
     type F = F;
-    fn distance(a: &[F; 2], b: &[F; 2]) -> F {
-        (a[0] - b[0]).abs() + (a[1] - b[1]).abs()
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        a.iter()
+            .zip(b.iter())
+            .fold(F::zero(), |sum, (&a, &b)| sum + (a - b).abs())
+    }
+
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        (query[axis] - key).abs()
+    }
+}
+
+/// Chebyshev Distance, the largest difference along any axis
+pub struct Chebyshev<F: Float> {
+    _marker: PhantomData<F>,
+}
+
+impl<F> Distance for Chebyshev<F>
+where
+    F: Float,
+{
+    type F = F;
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        a.iter()
+            .zip(b.iter())
+            .fold(F::zero(), |max, (&a, &b)| max.max((a - b).abs()))
     }
 
-    fn distance_point(a: &Point<Self::F>, b: &Point<Self::F>) -> Self::F {
-        Self::distance(&[a.x, a.y], &[b.x, b.y])
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        (query[axis] - key).abs()
+    }
+}
+
+/// Minkowski Distance of order P, P must be at least 1. Order 1 is the Manhattan distance, and
+/// order 2 is the Euclidean distance.
+pub struct Minkowski<F: Float, const P: u32> {
+    _marker: PhantomData<F>,
+}
+
+impl<F, const P: u32> Distance for Minkowski<F, P>
+where
+    F: Float,
+{
+    type F = F;
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        const { assert!(P >= 1, "Minkowski order must be at least 1") };
+        let p = P as i32;
+        a.iter()
+            .zip(b.iter())
+            .fold(F::zero(), |sum, (&a, &b)| sum + (a - b).abs().powi(p))
+            .powf(F::one() / F::from(P).unwrap())
+    }
+
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        (query[axis] - key).abs()
+    }
+}
+
+/// Mean radius of the Earth in metres
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Haversine Distance, the great-circle distance in metres between two (latitude, longitude)
+/// points in degrees, on a sphere with the mean radius of the Earth.
+///
+/// # Panics
+///
+/// Panics if the points are not 2d. A map in the geographic mode with other dimensions is
+/// rejected when it is constructed, see `MapConfig::validate`.
+pub struct Haversine<F: Float> {
+    _marker: PhantomData<F>,
+}
+
+impl<F> Distance for Haversine<F>
+where
+    F: Float,
+{
+    type F = F;
+    fn distance<const D: usize>(a: &[F; D], b: &[F; D]) -> F {
+        assert_eq!(
+            D, 2,
+            "Haversine distance needs (latitude, longitude) points"
+        );
+        let (lat1, lat2) = (a[0].to_radians(), b[0].to_radians());
+        let half_dlat = (lat2 - lat1) / F::from(2).unwrap();
+        let half_dlng = (b[1] - a[1]).to_radians() / F::from(2).unwrap();
+        let h = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlng.sin().powi(2);
+        F::from(2. * EARTH_RADIUS).unwrap() * h.sqrt().min(F::one()).asin()
+    }
+
+    /// The distance to the parallel of latitude `key` along the latitude, or to the meridian of
+    /// longitude `key` along the longitude.
+    ///
+    /// The longitude difference is not wrapped, so that the bound does not decrease as `key`
+    /// moves away, and it is a lower bound for differences up to 180 degrees. The queries of a
+    /// map wrap around the antimeridian through its periodic axis instead, see `Metric`.
+    fn lower_bound<const D: usize>(query: &[F; D], axis: usize, key: F) -> F {
+        assert_eq!(
+            D, 2,
+            "Haversine distance needs (latitude, longitude) points"
+        );
+        let radius = F::from(EARTH_RADIUS).unwrap();
+        let right_angle = F::from(90).unwrap();
+        let delta = (query[axis] - key).abs();
+        if axis == 0 {
+            return radius * delta.to_radians();
+        }
+        // The closest point of a meridian more than a right angle away is the nearest pole
+        let lat = query[0].to_radians().abs().min(right_angle.to_radians());
+        if delta >= right_angle {
+            radius * (right_angle.to_radians() - lat)
+        } else {
+            radius * (delta.to_radians().sin() * lat.cos()).min(F::one()).asin()
+        }
     }
 }
 
@@ -55,33 +202,123 @@ mod tests {
 
     #[test]
     fn test_euclidean_f32() {
-        let a = Point::<f32> { x: 0., y: 0. };
-        let b = Point::<f32> { x: 1., y: 1. };
+        let a = Point::<f32>::new(0., 0.);
+        let b = Point::<f32>::new(1., 1.);
         let d = Euclidean::distance_point(&a, &b);
         assert_delta!(d, std::f32::consts::SQRT_2, 0.00001);
     }
 
     #[test]
     fn test_euclidean_f64() {
-        let a = Point::<f64> { x: 0., y: 0. };
-        let b = Point::<f64> { x: 1., y: 1. };
+        let a = Point::<f64>::new(0., 0.);
+        let b = Point::<f64>::new(1., 1.);
         let d = Euclidean::distance_point(&a, &b);
         assert_delta!(d, std::f64::consts::SQRT_2, 0.00001);
     }
 
     #[test]
     fn test_manhattan_f32() {
-        let a = Point::<f32> { x: 0., y: 0. };
-        let b = Point::<f32> { x: 1., y: 1. };
+        let a = Point::<f32>::new(0., 0.);
+        let b = Point::<f32>::new(1., 1.);
         let d = Manhattan::distance_point(&a, &b);
         assert_delta!(d, 2., 0.00001);
     }
 
     #[test]
     fn test_manhattan_f64() {
-        let a = Point::<f64> { x: 0., y: 0. };
-        let b = Point::<f64> { x: 1., y: 1. };
+        let a = Point::<f64>::new(0., 0.);
+        let b = Point::<f64>::new(1., 1.);
         let d = Manhattan::distance_point(&a, &b);
         assert_delta!(d, 2., 0.00001);
     }
+
+    #[test]
+    fn test_euclidean_3d() {
+        let a = Point::from_coords([0., 0., 0.]);
+        let b = Point::from_coords([1., 2., 2.]);
+        assert_delta!(Euclidean::distance_point(&a, &b), 3., 0.00001);
+        assert_delta!(Manhattan::distance_point(&a, &b), 5., 0.00001);
+    }
+
+    #[test]
+    fn test_more_metrics() {
+        let a = [0., 0., 0.];
+        let b = [1., 2., -2.];
+        assert_delta!(SquaredEuclidean::distance(&a, &b), 9., 0.00001);
+        assert_delta!(Chebyshev::distance(&a, &b), 2., 0.00001);
+        assert_delta!(Minkowski::<f64, 1>::distance(&a, &b), 5., 0.00001);
+        assert_delta!(Minkowski::<f64, 2>::distance(&a, &b), 3., 0.00001);
+        assert_delta!(Minkowski::<f64, 3>::distance(&a, &b), 17f64.cbrt(), 0.00001);
+    }
+
+    #[test]
+    fn test_haversine() {
+        // Melbourne to Sydney
+        let melbourne = [-37.8136, 144.9631];
+        let sydney = [-33.8688, 151.2093];
+        assert_delta!(Haversine::distance(&melbourne, &sydney), 713_800., 500.);
+        assert_delta!(Haversine::distance(&sydney, &melbourne), 713_800., 500.);
+        assert_eq!(0., Haversine::distance(&sydney, &sydney));
+        // Across the antimeridian and between the poles
+        assert_delta!(
+            Haversine::distance(&[0., 179.5], &[0., -179.5]),
+            EARTH_RADIUS * 1f64.to_radians(),
+            0.001
+        );
+        assert_delta!(
+            Haversine::distance(&[90., 0.], &[-90., 0.]),
+            EARTH_RADIUS * std::f64::consts::PI,
+            0.001
+        );
+    }
+
+    #[test]
+    fn test_haversine_lower_bound() {
+        let query = [-37.8136, 144.9631];
+        let points = [
+            [-33.8688, 151.2093],
+            [-37., 145.5],
+            [-60., 170.],
+            [10., -170.],
+            [80., 20.],
+        ];
+        for axis in 0..2 {
+            for p in points.iter() {
+                let bound = Haversine::lower_bound(&query, axis, p[axis]);
+                assert!(bound <= Haversine::distance(&query, p));
+            }
+        }
+        // Along the longitude, the bound grows with the longitude difference, also past 180
+        // degrees
+        let bounds: Vec<f64> = (0..=360)
+            .map(|lng| Haversine::lower_bound(&query, 1, 144.9631 - lng as f64))
+            .collect();
+        assert!(bounds.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(bounds[180], bounds[360]);
+        assert_delta!(
+            bounds[180],
+            EARTH_RADIUS * (90. - 37.8136f64).to_radians(),
+            0.001
+        );
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let query = [1., 5.];
+        assert_eq!(2., Euclidean::lower_bound(&query, 1, 3.));
+        assert_eq!(4., SquaredEuclidean::lower_bound(&query, 1, 3.));
+        assert_eq!(2., Manhattan::lower_bound(&query, 1, 3.));
+        assert_eq!(2., Chebyshev::lower_bound(&query, 0, 3.));
+        assert_eq!(2., Minkowski::<f64, 3>::lower_bound(&query, 0, -1.));
+
+        // The default bound is the distance along a single axis
+        struct Scaled;
+        impl Distance for Scaled {
+            type F = f64;
+            fn distance<const D: usize>(a: &[f64; D], b: &[f64; D]) -> f64 {
+                2. * Manhattan::distance(a, b)
+            }
+        }
+        assert_eq!(4., Scaled::lower_bound(&query, 1, 3.));
+    }
 }
@@ -1,27 +1,88 @@
-use crate::geometry::Point;
+use crate::geometry::{Point, PointLike};
 use num_traits::float::Float;
 
-/// Extract all the x values from a Vec<Point<F>>
-pub fn extract_x<F: Float>(ps: &[Point<F>]) -> Vec<F> {
-    ps.iter().map(|p| p.x).collect()
+/// Extract all the values along the given axis from a slice of points
+pub fn extract_axis<F, P, const D: usize>(ps: &[P], axis: usize) -> Vec<F>
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    ps.iter().map(|p| p.coord(axis)).collect()
 }
 
-/// Extract all the y values from a Vec<Point<F>>
-pub fn extract_y<F: Float>(ps: &[Point<F>]) -> Vec<F> {
-    ps.iter().map(|p| p.y).collect()
+/// Extract all the x values from a slice of points
+pub fn extract_x<F, P, const D: usize>(ps: &[P]) -> Vec<F>
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    extract_axis(ps, 0)
 }
 
-/// Sort a Vec<Point<F>> based on the x values
-pub fn sort_by_x<F: Float>(ps: &mut [Point<F>]) {
-    ps.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+/// Extract all the y values from a slice of points
+pub fn extract_y<F, P, const D: usize>(ps: &[P]) -> Vec<F>
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    extract_axis(ps, 1)
 }
 
-/// Sort a Vec<Point<F>> based on the y values
-pub fn sort_by_y<F: Float>(ps: &mut [Point<F>]) {
-    ps.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+/// Sort a slice of points based on the values along the given axis
+pub fn sort_by_axis<F, P, const D: usize>(ps: &mut [P], axis: usize)
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    ps.sort_by(|a, b| a.coord(axis).partial_cmp(&b.coord(axis)).unwrap());
 }
 
-/// Convert a Vec of [F; 2] to a Vec<Point<F>>
-pub fn convert_to_points<F: Float>(ps: &[[F; 2]]) -> Option<Vec<Point<F>>> {
-    Some(ps.iter().map(|p| Point::new(p[0], p[1])).collect())
+/// Extract all the values along the given axis from a slice of points in parallel
+#[cfg(feature = "rayon")]
+pub fn par_extract_axis<F, P, const D: usize>(ps: &[P], axis: usize) -> Vec<F>
+where
+    F: Float + Send,
+    P: PointLike<F, D> + Sync,
+{
+    use rayon::prelude::*;
+    ps.par_iter().map(|p| p.coord(axis)).collect()
+}
+
+/// Sort a slice of points based on the values along the given axis in parallel. The sort is
+/// stable, so the order is identical to `sort_by_axis`.
+#[cfg(feature = "rayon")]
+pub fn par_sort_by_axis<F, P, const D: usize>(ps: &mut [P], axis: usize)
+where
+    F: Float,
+    P: PointLike<F, D> + Send,
+{
+    use rayon::prelude::*;
+    ps.par_sort_by(|a, b| a.coord(axis).partial_cmp(&b.coord(axis)).unwrap());
+}
+
+/// Sort a slice of points based on the x values
+pub fn sort_by_x<F, P, const D: usize>(ps: &mut [P])
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    sort_by_axis(ps, 0);
+}
+
+/// Sort a slice of points based on the y values
+pub fn sort_by_y<F, P, const D: usize>(ps: &mut [P])
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    sort_by_axis(ps, 1);
+}
+
+/// Convert a slice of points to a Vec<Point<F, D>>
+pub fn convert_to_points<F, P, const D: usize>(ps: &[P]) -> Option<Vec<Point<F, D>>>
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    Some(ps.iter().map(|p| Point::from_coords(p.coords())).collect())
 }
@@ -2,7 +2,11 @@ mod axis;
 pub mod distance;
 pub mod helper;
 mod point;
+mod point_like;
+mod polygon;
 
 pub use axis::*;
 pub use helper::*;
 pub use point::*;
+pub use point_like::*;
+pub use polygon::*;
@@ -1,20 +1,18 @@
 use num_traits::float::Float;
 
-/// Point struct contains id, x and y
+/// Point struct contains the coordinates of a point in D dimensions, 2d by default
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub struct Point<T> {
-    pub(crate) x: T,
-    pub(crate) y: T,
+pub struct Point<T, const D: usize = 2> {
+    pub(crate) coords: [T; D],
 }
 
-impl<T> Default for Point<T>
+impl<T, const D: usize> Default for Point<T, D>
 where
     T: Float,
 {
     fn default() -> Self {
         Point {
-            x: T::zero(),
-            y: T::zero(),
+            coords: [T::zero(); D],
         }
     }
 }
@@ -24,14 +22,106 @@ where
     T: Float,
 {
     pub fn new(x: T, y: T) -> Self {
-        Point { x, y }
+        Point { coords: [x, y] }
+    }
+}
+
+impl<T, const D: usize> Point<T, D>
+where
+    T: Float,
+{
+    /// Returns a point with the coordinates of all the D dimensions
+    pub fn from_coords(coords: [T; D]) -> Self {
+        Point { coords }
+    }
+
+    /// Returns the coordinates of the point
+    pub fn coords(&self) -> &[T; D] {
+        &self.coords
+    }
+
+    /// Returns the coordinate along the given axis
+    ///
+    /// # Arguments
+    /// * `axis` - The index of the dimension, panics if it is not less than D
+    pub fn coord(&self, axis: usize) -> T {
+        self.coords[axis]
     }
 
     pub fn x(&self) -> T {
-        self.x
+        self.coords[0]
     }
 
     pub fn y(&self) -> T {
-        self.y
+        self.coords[1]
+    }
+
+    /// Returns the third coordinate, panics if the point has less than three dimensions
+    pub fn z(&self) -> T {
+        self.coords[2]
+    }
+}
+
+impl<T, const D: usize> From<[T; D]> for Point<T, D>
+where
+    T: Float,
+{
+    fn from(coords: [T; D]) -> Self {
+        Point { coords }
+    }
+}
+
+/// Points are serialized as a tuple of their D coordinates
+#[cfg(feature = "serde")]
+impl<T, const D: usize> serde::Serialize for Point<T, D>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+
+        let mut tuple = serializer.serialize_tuple(D)?;
+        for coord in self.coords.iter() {
+            tuple.serialize_element(coord)?;
+        }
+        tuple.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, const D: usize> serde::Deserialize<'de> for Point<T, D>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        use core::{fmt, marker::PhantomData};
+        use serde::de::{Error, SeqAccess, Visitor};
+
+        struct CoordsVisitor<T, const D: usize>(PhantomData<T>);
+
+        impl<'de, T, const D: usize> Visitor<'de> for CoordsVisitor<T, D>
+        where
+            T: serde::Deserialize<'de>,
+        {
+            type Value = [T; D];
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a tuple of {D} coordinates")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut coords = Vec::with_capacity(D);
+                while let Some(coord) = seq.next_element()? {
+                    coords.push(coord);
+                }
+                let len = coords.len();
+                coords
+                    .try_into()
+                    .map_err(|_| A::Error::invalid_length(len, &self))
+            }
+        }
+
+        let coords = deserializer.deserialize_tuple(D, CoordsVisitor(PhantomData))?;
+        Ok(Point { coords })
     }
 }
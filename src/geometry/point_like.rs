@@ -0,0 +1,183 @@
+use crate::geometry::Point;
+use core::array;
+use num_traits::float::Float;
+
+/// PointLike is implemented by types that can be indexed by a LearnedHashMap as points with D
+/// coordinates, 2d by default.
+///
+/// Implement it for a record type to store the records in a LearnedHashMap directly, the
+/// coordinates of the record are used as the key for hashing and querying.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, PointLike};
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Shop {
+///     id: usize,
+///     lng: f64,
+///     lat: f64,
+/// }
+///
+/// impl PointLike<f64> for Shop {
+///     fn coord(&self, axis: usize) -> f64 {
+///         [self.lng, self.lat][axis]
+///     }
+///
+///     fn id(&self) -> Option<usize> {
+///         Some(self.id)
+///     }
+/// }
+///
+/// let mut shops = vec![
+///     Shop { id: 1, lng: 1., lat: 1. },
+///     Shop { id: 2, lng: 2., lat: 1. },
+///     Shop { id: 3, lng: 3., lat: 2. },
+/// ];
+/// let mut map = LearnedHashMap::<LinearModel<f64>, f64, Shop>::new();
+/// map.batch_insert(&mut shops).unwrap();
+///
+/// assert_eq!(map.get(&(2., 1.)).unwrap().id, 2);
+/// assert_eq!(map.nearest_neighbor(&[2.9, 2.]).unwrap().id(), Some(3));
+/// ```
+pub trait PointLike<F, const D: usize = 2> {
+    /// Returns the coordinate along the axis, for axis in 0..D
+    fn coord(&self, axis: usize) -> F;
+
+    /// Returns the coordinates of all the D dimensions
+    #[inline]
+    fn coords(&self) -> [F; D] {
+        array::from_fn(|axis| self.coord(axis))
+    }
+
+    /// Returns the id of the point, if it has one
+    #[inline]
+    fn id(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<F: Float, const D: usize> PointLike<F, D> for Point<F, D> {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        self.coords[axis]
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; D] {
+        self.coords
+    }
+}
+
+/// A pair of a point and a payload, the point is used as the key of the pair
+impl<F: Float, T, const D: usize> PointLike<F, D> for (Point<F, D>, T) {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        self.0.coords[axis]
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; D] {
+        self.0.coords
+    }
+}
+
+impl<F, P, const D: usize> PointLike<F, D> for &P
+where
+    P: PointLike<F, D> + ?Sized,
+{
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        (**self).coord(axis)
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; D] {
+        (**self).coords()
+    }
+
+    #[inline]
+    fn id(&self) -> Option<usize> {
+        (**self).id()
+    }
+}
+
+impl<F: Float, const D: usize> PointLike<F, D> for [F; D] {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        self[axis]
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; D] {
+        *self
+    }
+}
+
+impl<F: Float> PointLike<F> for (F, F) {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        match axis {
+            0 => self.0,
+            1 => self.1,
+            _ => panic!("axis {axis} out of 2 dimensions"),
+        }
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; 2] {
+        [self.0, self.1]
+    }
+}
+
+#[cfg(feature = "geo-types")]
+impl<F: Float + geo_types::CoordNum> PointLike<F> for geo_types::Coord<F> {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        PointLike::coords(self)[axis]
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; 2] {
+        [self.x, self.y]
+    }
+}
+
+#[cfg(feature = "geo-types")]
+impl<F: Float + geo_types::CoordNum> PointLike<F> for geo_types::Point<F> {
+    #[inline]
+    fn coord(&self, axis: usize) -> F {
+        PointLike::coords(self)[axis]
+    }
+
+    #[inline]
+    fn coords(&self) -> [F; 2] {
+        [self.x(), self.y()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coords() {
+        let p = Point::new(1., 2.);
+        assert_eq!([1., 2.], PointLike::coords(&p));
+        assert_eq!([1., 2.], PointLike::coords(&(p, "a")));
+        assert_eq!([1., 2.], PointLike::coords(&[1., 2.]));
+        assert_eq!([1., 2.], PointLike::coords(&(1., 2.)));
+        assert_eq!(2., (1., 2.).coord(1));
+        assert_eq!(None, PointLike::<f64>::id(&p));
+    }
+
+    #[cfg(feature = "geo-types")]
+    #[test]
+    fn geo_types_coords() {
+        let p = geo_types::Point::new(1., 2.);
+        let c = geo_types::coord! { x: 3., y: 4. };
+        assert_eq!([1., 2.], PointLike::coords(&p));
+        assert_eq!(4., PointLike::coord(&c, 1));
+    }
+}
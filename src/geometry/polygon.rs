@@ -0,0 +1,170 @@
+use num_traits::float::Float;
+
+/// Location of a point relative to a ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Inside,
+    Boundary,
+    Outside,
+}
+
+/// Returns the location of the point relative to the ring, a closed ring of vertices in either
+/// orientation. The last vertex may repeat the first one.
+///
+/// The points on an edge are found by an exact orientation test, and the other points by the
+/// even-odd rule with a horizontal ray. An edge only counts as crossed if its endpoints are on
+/// both sides of the ray, with the vertices on the ray counted above it, so a ray through a
+/// vertex crosses the ring once.
+fn locate<F: Float>(ring: &[[F; 2]], p: &[F; 2]) -> Location {
+    let [x, y] = *p;
+    let mut inside = false;
+    for (i, &a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        let cross = (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0]);
+        let within = x >= a[0].min(b[0])
+            && x <= a[0].max(b[0])
+            && y >= a[1].min(b[1])
+            && y <= a[1].max(b[1]);
+        if cross == F::zero() && within {
+            return Location::Boundary;
+        }
+        if (a[1] > y) != (b[1] > y) {
+            // The sign of cross tells on which side of the edge the point is, the edge crosses
+            // the ray on the right of the point if the point is on the left of an upward edge
+            let upward = b[1] > a[1];
+            if (cross > F::zero()) == upward {
+                inside = !inside;
+            }
+        }
+    }
+    if inside {
+        Location::Inside
+    } else {
+        Location::Outside
+    }
+}
+
+/// Returns true if the point is inside the polygon with the given exterior ring and holes, or on
+/// its boundary. The points inside a hole are outside of the polygon, and the points on the
+/// boundary of a hole are on the boundary of the polygon.
+///
+/// The rings are closed rings of vertices in either orientation, the last vertex may repeat the
+/// first one. A ring of less than 3 vertices contains no point.
+///
+/// # Arguments
+/// * `exterior` - The exterior ring of the polygon
+/// * `holes` - The rings of the holes of the polygon
+/// * `p` - The point
+///
+/// # Examples
+///
+/// ```
+/// use lsph::geometry::polygon_contains;
+/// let square = [[0., 0.], [4., 0.], [4., 4.], [0., 4.]];
+/// let hole = [[1., 1.], [3., 1.], [3., 3.], [1., 3.]];
+///
+/// assert!(polygon_contains(&square, &[hole], &[0.5, 2.]));
+/// assert!(polygon_contains(&square, &[hole], &[4., 2.]));
+/// assert!(!polygon_contains(&square, &[hole], &[2., 2.]));
+/// assert!(!polygon_contains(&square, &[hole], &[5., 2.]));
+/// ```
+pub fn polygon_contains<F, H>(exterior: &[[F; 2]], holes: &[H], p: &[F; 2]) -> bool
+where
+    F: Float,
+    H: AsRef<[[F; 2]]>,
+{
+    if exterior.len() < 3 || locate(exterior, p) == Location::Outside {
+        return false;
+    }
+    holes.iter().all(|hole| {
+        let hole = hole.as_ref();
+        hole.len() < 3 || locate(hole, p) != Location::Inside
+    })
+}
+
+/// Returns the bottom left and top right corners of the bounding box of the ring, or None if the
+/// ring is empty.
+pub fn ring_bounds<F: Float>(ring: &[[F; 2]]) -> Option<([F; 2], [F; 2])> {
+    let first = *ring.first()?;
+    Some(ring.iter().fold((first, first), |(min, max), v| {
+        (
+            [min[0].min(v[0]), min[1].min(v[1])],
+            [max[0].max(v[0]), max[1].max(v[1])],
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_HOLES: [[[f64; 2]; 0]; 0] = [];
+
+    #[test]
+    fn test_convex() {
+        let triangle = [[0., 0.], [4., 0.], [0., 4.]];
+        assert!(polygon_contains(&triangle, &NO_HOLES, &[1., 1.]));
+        assert!(polygon_contains(&triangle, &NO_HOLES, &[2., 2.]));
+        assert!(polygon_contains(&triangle, &NO_HOLES, &[0., 0.]));
+        assert!(!polygon_contains(&triangle, &NO_HOLES, &[2.1, 2.]));
+        assert!(!polygon_contains(&triangle, &NO_HOLES, &[-1., 0.]));
+
+        // Orientation and a repeated closing vertex do not matter
+        let reversed = [[0., 0.], [0., 4.], [4., 0.], [0., 0.]];
+        assert!(polygon_contains(&reversed, &NO_HOLES, &[1., 1.]));
+        assert!(!polygon_contains(&reversed, &NO_HOLES, &[3., 3.]));
+
+        assert!(!polygon_contains(
+            &[[0., 0.], [1., 1.]],
+            &NO_HOLES,
+            &[0., 0.]
+        ));
+    }
+
+    #[test]
+    fn test_concave_through_vertices() {
+        // A comb, the rays through the teeth pass through its vertices
+        let comb = [
+            [0., 0.],
+            [5., 0.],
+            [5., 2.],
+            [4., 2.],
+            [4., 1.],
+            [3., 1.],
+            [3., 2.],
+            [2., 2.],
+            [2., 1.],
+            [1., 1.],
+            [1., 2.],
+            [0., 2.],
+        ];
+        assert!(polygon_contains(&comb, &NO_HOLES, &[0.5, 1.]));
+        assert!(polygon_contains(&comb, &NO_HOLES, &[2.5, 1.5]));
+        assert!(polygon_contains(&comb, &NO_HOLES, &[1.5, 1.]));
+        assert!(!polygon_contains(&comb, &NO_HOLES, &[1.5, 1.5]));
+        assert!(!polygon_contains(&comb, &NO_HOLES, &[3.5, 1.5]));
+        assert!(!polygon_contains(&comb, &NO_HOLES, &[-0.5, 2.]));
+        assert!(!polygon_contains(&comb, &NO_HOLES, &[-0.5, 1.]));
+    }
+
+    #[test]
+    fn test_holes() {
+        let square = [[0., 0.], [10., 0.], [10., 10.], [0., 10.]];
+        let holes = vec![
+            vec![[1., 1.], [3., 1.], [3., 3.], [1., 3.]],
+            vec![[5., 5.], [8., 5.], [8., 8.]],
+        ];
+        assert!(polygon_contains(&square, &holes, &[4., 4.]));
+        assert!(polygon_contains(&square, &holes, &[1., 2.]));
+        assert!(!polygon_contains(&square, &holes, &[2., 2.]));
+        assert!(!polygon_contains(&square, &holes, &[7., 6.]));
+        assert!(polygon_contains(&square, &holes, &[6., 7.]));
+    }
+
+    #[test]
+    fn test_ring_bounds() {
+        let ring = [[1., 5.], [-2., 3.], [4., -1.]];
+        assert_eq!(Some(([-2., -1.], [4., 5.])), ring_bounds(&ring));
+        assert_eq!(None, ring_bounds::<f64>(&[]));
+    }
+}
@@ -2,12 +2,15 @@ use crate::models::Model;
 use num_traits::cast::{AsPrimitive, FromPrimitive};
 use num_traits::float::Float;
 
-/// LearnedHasher takes a model and produces hash from the model
+/// LearnedHasher takes a model and produces hash from the model.
+///
+/// Hashing is a pure function of the model, so a LearnedHasher can be shared across threads
+/// once the model is trained.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LearnedHasher<M> {
-    state: u64,
     pub model: M,
-    sort_by_x: bool,
+    axis: usize,
 }
 
 impl<M, F> Default for LearnedHasher<M>
@@ -18,9 +21,8 @@ where
     #[inline]
     fn default() -> Self {
         Self {
-            state: 0,
             model: Default::default(),
-            sort_by_x: true,
+            axis: 0,
         }
     }
 }
@@ -42,29 +44,25 @@ where
     /// ```
     #[inline]
     pub fn with_model(model: M) -> Self {
-        Self {
-            state: 0,
-            model,
-            sort_by_x: true,
-        }
+        Self { model, axis: 0 }
     }
 
-    /// Returns a current Hasher state.
+    /// Returns the index of the axis that the points are hashed and sorted along.
     #[inline]
-    fn finish(&self) -> u64 {
-        self.state
+    pub fn axis(&self) -> usize {
+        self.axis
     }
 
-    /// Returns the sorted index base on parameter self.sort_by_x.
+    /// Sets the index of the axis that the points are hashed and sorted along.
     #[inline]
-    pub fn sort_by_x(&self) -> bool {
-        self.sort_by_x
+    pub fn set_axis(&mut self, axis: usize) {
+        self.axis = axis;
     }
 
-    /// Sets self.sort_by_x to a given boolean value.
+    /// Returns true if the points are hashed and sorted along the x axis.
     #[inline]
-    pub fn set_sort_by_x(&mut self, x: bool) {
-        self.sort_by_x = x;
+    pub fn sort_by_x(&self) -> bool {
+        self.axis == 0
     }
 }
 
@@ -85,10 +83,10 @@ where
     F: Float + AsPrimitive<u64>,
     M: Model<F = F>,
 {
-    /// Writes a data into self.data by inferencing the input data into the trained model.
+    /// Returns the hash value of a data by inferencing the input data into the trained model.
     #[inline]
-    fn write(&mut self, data: &F) {
-        self.state = self.model.predict(*data).floor().as_();
+    fn hash(&self, data: &F) -> u64 {
+        self.model.predict(*data).floor().as_()
     }
 }
 
@@ -103,7 +101,7 @@ where
     /// # Arguments
     /// * `hash` - An usize hash value
     #[inline]
-    fn unwrite(&mut self, hash: u64) -> F {
+    fn unwrite(&self, hash: u64) -> F {
         let hash = FromPrimitive::from_u64(hash).unwrap();
         self.model.unpredict(hash)
     }
@@ -113,31 +111,27 @@ where
 /// # Arguments
 /// * `hasher` - A LearnedHasher type
 #[inline]
-pub fn make_hash<M, F>(hasher: &mut LearnedHasher<M>, p: &F) -> u64
+pub fn make_hash<M, F>(hasher: &LearnedHasher<M>, p: &F) -> u64
 where
     F: Float + FromPrimitive + AsPrimitive<u64>,
     M: Model<F = F> + Default,
 {
-    hasher.write(p);
-    hasher.finish()
+    hasher.hash(p)
 }
 
-/// Make hash value from a given hasher, and 2 item array with float data.
+/// Make hash value from a given hasher, and D item array with float data. The value along the
+/// axis of the hasher is hashed.
 ///
 /// # Arguments
 /// * `hasher` - A LearnedHasher type
 /// * `p` - Point data
 #[inline]
-pub fn make_hash_point<M, F>(hasher: &mut LearnedHasher<M>, p: &[F; 2]) -> u64
+pub fn make_hash_point<M, F, const D: usize>(hasher: &LearnedHasher<M>, p: &[F; D]) -> u64
 where
     F: Float + FromPrimitive + AsPrimitive<u64>,
     M: Model<F = F> + Default,
 {
-    if hasher.sort_by_x {
-        make_hash(hasher, &p[0])
-    } else {
-        make_hash(hasher, &p[1])
-    }
+    make_hash(hasher, &p[hasher.axis])
 }
 
 /// Unmake hash value from a given hasher, and a u64 hash value.
@@ -147,7 +141,7 @@ where
 /// * `hasher` - A LearnedHasher type
 /// * `p` - Point data
 #[inline]
-pub fn unhash<M, F>(hasher: &mut LearnedHasher<M>, hash: u64) -> F
+pub fn unhash<M, F>(hasher: &LearnedHasher<M>, hash: u64) -> F
 where
     F: Float + FromPrimitive + AsPrimitive<u64>,
     M: Model<F = F> + Default,
@@ -162,19 +156,29 @@ mod tests {
 
     #[test]
     fn hasher_with_empty_model() {
-        let mut hasher: LearnedHasher<LinearModel<f64>> = LearnedHasher::new();
-        hasher.write(&10f64);
-        assert_eq!(0u64, hasher.finish());
+        let hasher: LearnedHasher<LinearModel<f64>> = LearnedHasher::new();
+        assert_eq!(0u64, hasher.hash(&10f64));
     }
 
     #[test]
     fn unhash() {
-        let mut hasher: LearnedHasher<LinearModel<f64>> = LearnedHasher::with_model(LinearModel {
+        let hasher: LearnedHasher<LinearModel<f64>> = LearnedHasher::with_model(LinearModel {
             coefficient: 3.,
             intercept: 2.,
         });
-        hasher.write(&10.5);
-        assert_eq!(33u64, hasher.finish());
+        assert_eq!(33u64, hasher.hash(&10.5));
         assert_delta!(10.33f64, hasher.unwrite(33u64), 0.01);
     }
+
+    #[test]
+    fn hash_along_axis() {
+        let mut hasher: LearnedHasher<LinearModel<f64>> = LearnedHasher::with_model(LinearModel {
+            coefficient: 1.,
+            intercept: 0.,
+        });
+        assert_eq!(1u64, super::make_hash_point(&hasher, &[1.5, 2.5, 3.5]));
+        hasher.set_axis(2);
+        assert!(!hasher.sort_by_x());
+        assert_eq!(3u64, super::make_hash_point(&hasher, &[1.5, 2.5, 3.5]));
+    }
 }
@@ -22,7 +22,7 @@
 //! ```
 //! use lsph::{LearnedHashMap, LinearModel};
 //! let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-//! let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+//! let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
 //!
 //! assert_eq!(map.get(&[1., 1.]).is_some(), true);
 //! assert_eq!(map.get(&[3., 1.]).is_none(), true);
@@ -31,3 +31,12 @@ macro_rules! assert_empty {
         }
     };
 }
+
+#[macro_export]
+macro_rules! assert_finite {
+    ($a:expr) => {
+        if !$a.iter().all(|(x, y)| x.is_finite() && y.is_finite()) {
+            return Err(Error::NonFiniteCoordinate);
+        }
+    };
+}
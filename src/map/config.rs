@@ -0,0 +1,408 @@
+use crate::{
+    error::Error,
+    geometry::{Point, PointLike},
+    hasher::LearnedHasher,
+    map::{LearnedHashMap, RetrainPolicy},
+    models::Model,
+};
+use core::{fmt::Debug, iter::Sum, marker::PhantomData};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+
+/// Default number of buckets allocated by the first insert
+const INITIAL_NBUCKETS: usize = 1;
+
+/// Metric of the radius and nearest neighbor queries of a LearnedHashMap, see
+/// `MapConfig::metric`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Metric {
+    /// Planar Euclidean distance in the unit of the coordinates
+    #[default]
+    Euclidean,
+
+    /// Geographic mode, the points are (latitude, longitude) in degrees and the distances are
+    /// great-circle distances in metres, see `Haversine`. The points must be 2d. The longitude
+    /// is the periodic axis unless `MapConfig::periodic` sets one, so the queries wrap around
+    /// the antimeridian and the inserts reject the longitudes outside of -180..=180.
+    Haversine,
+}
+
+/// A periodic axis of the points, e.g. the longitude, see `MapConfig::periodic`. The coordinates
+/// along the axis are in `min..=max`, and `min` and `max` are the same place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeriodicAxis {
+    /// Index of the periodic axis
+    pub axis: usize,
+
+    /// Lower bound of the coordinates along the axis
+    pub min: f64,
+
+    /// Upper bound of the coordinates along the axis
+    pub max: f64,
+}
+
+impl PeriodicAxis {
+    /// Returns the periodic axis at the given index, with coordinates in `min..=max`.
+    #[inline]
+    pub fn new(axis: usize, min: f64, max: f64) -> Self {
+        Self { axis, min, max }
+    }
+
+    /// Returns the longitude of (latitude, longitude) points in degrees, axis 1 in -180..=180.
+    #[inline]
+    pub fn longitude() -> Self {
+        Self::new(1, -180., 180.)
+    }
+
+    /// Returns the length of the period.
+    #[inline]
+    pub fn period(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// Returns true if the coordinate is in the domain of the axis.
+    #[inline]
+    pub fn contains(&self, coord: f64) -> bool {
+        coord >= self.min && coord <= self.max
+    }
+}
+
+/// MapConfig holds the tuning parameters of a LearnedHashMap.
+///
+/// The inline capacity of the buckets is a type parameter of the map instead, see
+/// `LearnedHashMapBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapConfig {
+    /// The table grows when an insert finds more items than `load_factor` times the number of
+    /// buckets, 0.75 by default
+    pub load_factor: f64,
+
+    /// The factor the number of buckets is multiplied by when the table grows, 2 by default
+    pub growth_factor: f64,
+
+    /// Number of buckets allocated by the first insert into an empty table, 1 by default
+    pub initial_buckets: usize,
+
+    /// Axis the model is trained along, or None to train along the axis with the largest
+    /// variance
+    pub axis: Option<usize>,
+
+    /// Policy deciding when the model is retrained after an insert
+    pub retrain_policy: RetrainPolicy,
+
+    /// An insert replaces the closest entry within the given Euclidean distance, so that the
+    /// map holds no two points closer than the tolerance. None by default, which only replaces
+    /// entries with the same point. Batch inserts are not deduplicated.
+    pub dedup_tolerance: Option<f64>,
+
+    /// Metric of `radius_range`, `radius_range_with_distance`, `nearest_neighbor` and
+    /// `k_nearest_neighbors`, Euclidean by default. With `Metric::Haversine` the radius and the
+    /// distances are in metres.
+    pub metric: Metric,
+
+    /// A periodic axis, e.g. the longitude, or None by default. The range, radius and nearest
+    /// neighbor queries crossing the boundary of the axis wrap around it: a range whose bottom
+    /// left is larger than its top right along the axis covers both ends of the domain, and the
+    /// distances along the axis are measured the short way around. Inserts reject the points
+    /// outside of the domain.
+    pub periodic: Option<PeriodicAxis>,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            load_factor: 0.75,
+            growth_factor: 2.,
+            initial_buckets: INITIAL_NBUCKETS,
+            axis: None,
+            retrain_policy: RetrainPolicy::default(),
+            dedup_tolerance: None,
+            metric: Metric::Euclidean,
+            periodic: None,
+        }
+    }
+}
+
+impl MapConfig {
+    /// Checks the configuration for points of D dimensions.
+    ///
+    /// # Errors
+    ///
+    /// Returns Error::InvalidConfig if
+    ///
+    /// * the load factor is not positive and finite
+    /// * the growth factor is not finite or not larger than 1
+    /// * the initial number of buckets is 0
+    /// * the axis is not less than D
+    /// * the dedup tolerance is negative or not finite
+    /// * the metric is `Metric::Haversine` and D is not 2
+    /// * the periodic axis is not less than D, or its bounds are not finite and increasing
+    pub fn validate<const D: usize>(&self) -> Result<(), Error> {
+        let valid = self.load_factor.is_finite()
+            && self.load_factor > 0.
+            && self.growth_factor.is_finite()
+            && self.growth_factor > 1.
+            && self.initial_buckets > 0
+            && self.axis.is_none_or(|axis| axis < D)
+            && self
+                .dedup_tolerance
+                .is_none_or(|tolerance| tolerance.is_finite() && tolerance >= 0.)
+            && (self.metric != Metric::Haversine || D == 2)
+            && self.periodic.is_none_or(|periodic| {
+                periodic.axis < D
+                    && periodic.min.is_finite()
+                    && periodic.max.is_finite()
+                    && periodic.min < periodic.max
+            });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig)
+        }
+    }
+
+    /// Returns the periodic axis of the map, which is `PeriodicAxis::longitude` in the
+    /// geographic mode if `periodic` is None.
+    #[inline]
+    pub fn periodic_axis(&self) -> Option<PeriodicAxis> {
+        self.periodic
+            .or((self.metric == Metric::Haversine).then(PeriodicAxis::longitude))
+    }
+
+    /// Returns true if a table of the given number of buckets holding the given number of items
+    /// needs to grow before the next insert.
+    #[inline]
+    pub(crate) fn needs_growth(&self, items: usize, n_buckets: usize) -> bool {
+        n_buckets == 0 || items as f64 > self.load_factor * n_buckets as f64
+    }
+
+    /// Returns the number of buckets a table of the given number of buckets grows to, which is
+    /// always larger than the current number.
+    #[inline]
+    pub(crate) fn grown(&self, n_buckets: usize) -> Result<usize, Error> {
+        if n_buckets == 0 {
+            return Ok(self.initial_buckets);
+        }
+        let target = (n_buckets as f64 * self.growth_factor).ceil();
+        if target >= usize::MAX as f64 {
+            return Err(Error::CapacityOverflow);
+        }
+        Ok((target as usize).max(n_buckets + 1))
+    }
+}
+
+/// LearnedHashMapBuilder builds a LearnedHashMap with a custom hasher and configuration.
+///
+/// The const generic `N` sets the number of entries stored inline in every bucket before the
+/// bucket allocates, which trades memory for fewer allocations.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, Point};
+/// let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64>, 2, 16>::builder()
+///     .load_factor(2.)
+///     .growth_factor(1.5)
+///     .initial_buckets(64)
+///     .axis(0)
+///     .build()
+///     .unwrap();
+/// map.insert(Point::new(1., 1.)).unwrap();
+///
+/// assert_eq!(map.len(), 64);
+/// assert_eq!(map.config().load_factor, 2.);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LearnedHashMapBuilder<M, F, V = Point<F>, const D: usize = 2, const N: usize = 6> {
+    hasher: LearnedHasher<M>,
+    config: MapConfig,
+    _marker: PhantomData<(F, V)>,
+}
+
+impl<M, F, V, const D: usize, const N: usize> Default for LearnedHashMapBuilder<M, F, V, D, N>
+where
+    F: Float,
+    M: Model<F = F> + Default,
+{
+    fn default() -> Self {
+        Self {
+            hasher: LearnedHasher::new(),
+            config: MapConfig::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMapBuilder<M, F, V, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Returns a builder with the default hasher and configuration.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the hasher of the map.
+    #[inline]
+    pub fn hasher(mut self, hasher: LearnedHasher<M>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Sets the whole configuration of the map.
+    #[inline]
+    pub fn config(mut self, config: MapConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the load factor, see `MapConfig::load_factor`.
+    #[inline]
+    pub fn load_factor(mut self, load_factor: f64) -> Self {
+        self.config.load_factor = load_factor;
+        self
+    }
+
+    /// Sets the growth factor, see `MapConfig::growth_factor`.
+    #[inline]
+    pub fn growth_factor(mut self, growth_factor: f64) -> Self {
+        self.config.growth_factor = growth_factor;
+        self
+    }
+
+    /// Sets the initial number of buckets, see `MapConfig::initial_buckets`.
+    #[inline]
+    pub fn initial_buckets(mut self, initial_buckets: usize) -> Self {
+        self.config.initial_buckets = initial_buckets;
+        self
+    }
+
+    /// Forces the model to be trained along the given axis, see `MapConfig::axis`.
+    #[inline]
+    pub fn axis(mut self, axis: usize) -> Self {
+        self.config.axis = Some(axis);
+        self
+    }
+
+    /// Sets the retrain policy, see `MapConfig::retrain_policy`.
+    #[inline]
+    pub fn retrain_policy(mut self, policy: RetrainPolicy) -> Self {
+        self.config.retrain_policy = policy;
+        self
+    }
+
+    /// Sets the dedup tolerance, see `MapConfig::dedup_tolerance`.
+    #[inline]
+    pub fn dedup_tolerance(mut self, tolerance: f64) -> Self {
+        self.config.dedup_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Sets the metric of the distance queries, see `MapConfig::metric`.
+    #[inline]
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.config.metric = metric;
+        self
+    }
+
+    /// Sets the periodic axis, see `MapConfig::periodic`.
+    #[inline]
+    pub fn periodic(mut self, periodic: PeriodicAxis) -> Self {
+        self.config.periodic = Some(periodic);
+        self
+    }
+
+    /// Builds an empty map.
+    ///
+    /// # Errors
+    ///
+    /// Returns Error::InvalidConfig if the configuration is out of range, see
+    /// `MapConfig::validate`.
+    pub fn build(self) -> Result<LearnedHashMap<M, F, V, D, N>, Error> {
+        let mut map = LearnedHashMap::with_config(self.config)?;
+        map.hasher = self.hasher;
+        if let Some(axis) = self.config.axis {
+            map.hasher.set_axis(axis);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        assert_eq!(Ok(()), MapConfig::default().validate::<2>());
+        let invalid = [
+            MapConfig {
+                load_factor: 0.,
+                ..Default::default()
+            },
+            MapConfig {
+                load_factor: f64::NAN,
+                ..Default::default()
+            },
+            MapConfig {
+                growth_factor: 1.,
+                ..Default::default()
+            },
+            MapConfig {
+                initial_buckets: 0,
+                ..Default::default()
+            },
+            MapConfig {
+                axis: Some(2),
+                ..Default::default()
+            },
+            MapConfig {
+                dedup_tolerance: Some(-1.),
+                ..Default::default()
+            },
+            MapConfig {
+                periodic: Some(PeriodicAxis::new(2, -180., 180.)),
+                ..Default::default()
+            },
+            MapConfig {
+                periodic: Some(PeriodicAxis::new(1, 180., -180.)),
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert_eq!(Err(Error::InvalidConfig), config.validate::<2>());
+        }
+
+        let geographic = MapConfig {
+            metric: Metric::Haversine,
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), geographic.validate::<2>());
+        assert_eq!(Err(Error::InvalidConfig), geographic.validate::<3>());
+    }
+
+    #[test]
+    fn growth() {
+        let config = MapConfig {
+            growth_factor: 1.1,
+            initial_buckets: 4,
+            ..Default::default()
+        };
+        assert_eq!(Ok(4), config.grown(0));
+        assert_eq!(Ok(2), config.grown(1));
+        assert_eq!(Ok(11), config.grown(10));
+        assert_eq!(Err(Error::CapacityOverflow), config.grown(usize::MAX));
+
+        assert!(config.needs_growth(0, 0));
+        assert!(!config.needs_growth(3, 4));
+        assert!(config.needs_growth(4, 4));
+    }
+}
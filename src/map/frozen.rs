@@ -0,0 +1,645 @@
+use crate::{
+    geometry::{distance::*, Point, PointLike},
+    hasher::LearnedHasher,
+    map::{
+        query,
+        query::{Buckets, Query},
+        table::{Bucket, Table},
+        LearnedHashMap, MapConfig,
+    },
+    models::Model,
+};
+use core::{array, fmt::Debug, iter::Sum, marker::PhantomData, slice};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+
+/// FrozenLearnedHashMap is an immutable LearnedHashMap optimized for queries.
+///
+/// The entries are stored CSR-style: all the entries are in one contiguous array in bucket
+/// order, and the entries of bucket `i` are in `offsets[i]..offsets[i + 1]`. Scanning
+/// neighboring buckets reads contiguous memory, instead of following a pointer per bucket.
+///
+/// A FrozenLearnedHashMap is created by `LearnedHashMap::freeze`, and turned back into a
+/// mutable map by `thaw`.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, Point};
+/// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+/// let (map, _) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+///
+/// let frozen = map.freeze();
+/// assert_eq!(frozen.get(&[2., 1.]), Some(&Point::new(2., 1.)));
+/// assert_eq!(frozen.range_search(&[0., 0.], &[3., 3.]).unwrap().len(), 3);
+/// assert_eq!(frozen.nearest_neighbor(&[2.9, 2.]), Some(Point::new(3., 2.)));
+///
+/// let mut map = frozen.thaw();
+/// map.insert(Point::new(5., 5.)).unwrap();
+/// assert_eq!(map.items(), 5);
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "FrozenParts<M, V>",
+        bound(deserialize = "M: Model<F = F> + serde::Deserialize<'de>, \
+                             F: Float, V: serde::Deserialize<'de>")
+    )
+)]
+pub struct FrozenLearnedHashMap<M, F, V = Point<F>, const D: usize = 2, const N: usize = 6> {
+    hasher: LearnedHasher<M>,
+    offsets: Vec<usize>,
+    entries: Vec<V>,
+    config: MapConfig,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _marker: PhantomData<F>,
+}
+
+/// The fields of a deserialized FrozenLearnedHashMap, before they are validated
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct FrozenParts<M, V> {
+    hasher: LearnedHasher<M>,
+    offsets: Vec<usize>,
+    entries: Vec<V>,
+    config: MapConfig,
+}
+
+#[cfg(feature = "serde")]
+impl<M, F, V, const D: usize, const N: usize> TryFrom<FrozenParts<M, V>>
+    for FrozenLearnedHashMap<M, F, V, D, N>
+where
+    F: Float,
+    M: Model<F = F>,
+{
+    type Error = &'static str;
+
+    /// Checks that the bucket offsets increase from 0 to the number of entries, and that the
+    /// configuration and the axis of the hasher are valid for the dimensions, so that the
+    /// queries cannot index out of the entries.
+    fn try_from(parts: FrozenParts<M, V>) -> Result<Self, Self::Error> {
+        let offsets = &parts.offsets;
+        if offsets.first() != Some(&0)
+            || offsets.windows(2).any(|w| w[0] > w[1])
+            || offsets.last() != Some(&parts.entries.len())
+        {
+            return Err("bucket offsets do not match the entries");
+        }
+        if parts.config.validate::<D>().is_err() || parts.hasher.axis() >= D {
+            return Err("map configuration is out of range");
+        }
+        Ok(FrozenLearnedHashMap {
+            hasher: parts.hasher,
+            offsets: parts.offsets,
+            entries: parts.entries,
+            config: parts.config,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMap<M, F, V, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Freezes the map into a FrozenLearnedHashMap, which stores the entries contiguously for
+    /// faster queries. The buckets, the order of the entries, the configuration and the bucket
+    /// inline capacity are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, _) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// let frozen = map.freeze();
+    /// assert_eq!(frozen.items(), 4);
+    /// ```
+    pub fn freeze(mut self) -> FrozenLearnedHashMap<M, F, V, D, N> {
+        self.finish_migration();
+        let mut offsets = Vec::with_capacity(self.table.len() + 1);
+        let mut entries = Vec::with_capacity(self.items);
+        offsets.push(0);
+        for bucket in self.table.iter_mut() {
+            entries.extend(bucket.drain(..));
+            offsets.push(entries.len());
+        }
+        FrozenLearnedHashMap {
+            hasher: self.hasher,
+            offsets,
+            entries,
+            config: self.config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> FrozenLearnedHashMap<M, F, V, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Thaws the map back into a mutable LearnedHashMap, with the same buckets, configuration
+    /// and bucket inline capacity.
+    pub fn thaw(self) -> LearnedHashMap<M, F, V, D, N> {
+        let items = self.entries.len();
+        let mut entries = self.entries.into_iter();
+        let mut table = Table::with_capacity(self.offsets.len() - 1);
+        table.extend(self.offsets.windows(2).map(|w| {
+            let mut bucket = Bucket::new();
+            bucket.extend(entries.by_ref().take(w[1] - w[0]));
+            bucket
+        }));
+        LearnedHashMap {
+            hasher: self.hasher,
+            probes: table.probes(),
+            table,
+            items,
+            migration: None,
+            config: self.config,
+            inserts: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the hasher of the map.
+    #[inline]
+    pub fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    /// Returns the configuration of the map it was frozen from.
+    #[inline]
+    pub fn config(&self) -> &MapConfig {
+        &self.config
+    }
+
+    /// Returns the front end of the queries over the buckets.
+    #[inline]
+    fn query(&self) -> Query<'_, F, Self, Self, D> {
+        Query::new(self, None, self.config.metric, self.config.periodic_axis())
+    }
+
+    /// Returns Option<&V> with given point data.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    #[inline]
+    pub fn get<P: PointLike<F, D>>(&self, p: &P) -> Option<&V> {
+        query::find(self, &p.coords())
+    }
+
+    /// Returns Option<&V> of the closest entry within epsilon of the given point, measured by
+    /// Euclidean distance.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    /// * `epsilon` - The largest distance to the point
+    #[inline]
+    pub fn get_approx<P: PointLike<F, D>>(&self, p: &P, epsilon: F) -> Option<&V> {
+        query::find_approx(self, &p.coords(), epsilon).map(|(e, _)| e)
+    }
+
+    /// Returns bool if the map contains the point.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    #[inline]
+    pub fn contains_points<P: PointLike<F, D>>(&self, p: &P) -> bool {
+        self.get(p).is_some()
+    }
+
+    /// Returns usize number of buckets.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns usize number of items.
+    #[inline]
+    pub fn items(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns bool if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries, in bucket order.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, V> {
+        self.entries.iter()
+    }
+
+    /// Returns all the entries within the given range, see `LearnedHashMap::range_search`.
+    ///
+    /// # Arguments
+    /// * `bottom_left` - A point-like data that represents the bottom left of the range.
+    /// * `top_right` - A point-like data that represents the top right of the range.
+    pub fn range_search<P: PointLike<F, D>>(
+        &self,
+        bottom_left: &P,
+        top_right: &P,
+    ) -> Option<Vec<V>> {
+        let result: Vec<V> = self
+            .query()
+            .range(&bottom_left.coords(), &top_right.coords())
+            .into_iter()
+            .cloned()
+            .collect();
+        if result.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Returns all the entries within the bounding square of the given radius, see
+    /// `LearnedHashMap::square_range`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value, which is half of the side of the square
+    #[inline]
+    pub fn square_range<P: PointLike<F, D>>(&self, query_point: &P, radius: F) -> Option<Vec<V>> {
+        let query_point = query_point.coords();
+        self.range_search(
+            &array::from_fn(|i| query_point[i] - radius),
+            &array::from_fn(|i| query_point[i] + radius),
+        )
+    }
+
+    /// Returns all the entries within the given radius of the query point, see
+    /// `LearnedHashMap::radius_range`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    #[inline]
+    pub fn radius_range<P: PointLike<F, D>>(&self, query_point: &P, radius: F) -> Option<Vec<V>> {
+        let result: Vec<V> = self
+            .radius_range_with_distance(query_point, radius)?
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect();
+        Some(result)
+    }
+
+    /// Returns all the entries within the given radius of the query point paired with their
+    /// distance, see `LearnedHashMap::radius_range_with_distance`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    #[inline]
+    pub fn radius_range_with_distance<P: PointLike<F, D>>(
+        &self,
+        query_point: &P,
+        radius: F,
+    ) -> Option<Vec<(V, F)>> {
+        let result: Vec<(V, F)> = self
+            .query()
+            .radius(&query_point.coords(), radius)
+            .into_iter()
+            .map(|(e, d)| (e.clone(), d))
+            .collect();
+        if result.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Returns all the entries within the given radius of the query point measured by the
+    /// distance Dist, paired with their distance, see `LearnedHashMap::radius_range_with`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    pub fn radius_range_with<Dist, P>(&self, query_point: &P, radius: F) -> Option<Vec<(V, F)>>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        let result: Vec<(V, F)> = self
+            .query()
+            .radius_with::<Dist>(&query_point.coords(), radius)
+            .into_iter()
+            .map(|(e, d)| (e.clone(), d))
+            .collect();
+        if result.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Returns the closest entry to the query point, see `LearnedHashMap::nearest_neighbor`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    #[inline]
+    pub fn nearest_neighbor<P: PointLike<F, D>>(&self, query_point: &P) -> Option<V> {
+        self.k_nearest_neighbors(query_point, 1).pop()
+    }
+
+    /// Returns the k closest entries to the query point ordered by distance, see
+    /// `LearnedHashMap::k_nearest_neighbors`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    #[inline]
+    pub fn k_nearest_neighbors<P: PointLike<F, D>>(&self, query_point: &P, k: usize) -> Vec<V> {
+        self.query()
+            .nearest(&query_point.coords(), k, |_| true)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the closest entry to the query point measured by the distance Dist, see
+    /// `LearnedHashMap::nearest_neighbor_with`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    #[inline]
+    pub fn nearest_neighbor_with<Dist, P>(&self, query_point: &P) -> Option<V>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        self.k_nearest_neighbors_with::<Dist, P>(query_point, 1)
+            .pop()
+    }
+
+    /// Returns the k closest entries to the query point measured by the distance Dist, see
+    /// `LearnedHashMap::k_nearest_neighbors_with`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    #[inline]
+    pub fn k_nearest_neighbors_with<Dist, P>(&self, query_point: &P, k: usize) -> Vec<V>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        self.query()
+            .nearest_with::<Dist, _>(&query_point.coords(), k, |_| true)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the closest entry matching the predicate to the query point, see
+    /// `LearnedHashMap::nearest_neighbor_where`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    /// * `predicate` - Returns true for the entries to consider
+    #[inline]
+    pub fn nearest_neighbor_where<P, G>(&self, query_point: &P, predicate: G) -> Option<V>
+    where
+        P: PointLike<F, D>,
+        G: FnMut(&V) -> bool,
+    {
+        self.k_nearest_neighbors_where(query_point, 1, predicate)
+            .pop()
+    }
+
+    /// Returns the k closest entries matching the predicate to the query point ordered by
+    /// distance, see `LearnedHashMap::k_nearest_neighbors_where`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    /// * `predicate` - Returns true for the entries to consider
+    #[inline]
+    pub fn k_nearest_neighbors_where<P, G>(
+        &self,
+        query_point: &P,
+        k: usize,
+        mut predicate: G,
+    ) -> Vec<V>
+    where
+        P: PointLike<F, D>,
+        G: FnMut(&V) -> bool,
+    {
+        self.query()
+            .nearest(&query_point.coords(), k, |e| predicate(e))
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+}
+
+impl<M, F, V, const N: usize> FrozenLearnedHashMap<M, F, V, 2, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, 2> + Clone,
+{
+    /// Returns all the entries inside the given polygon, see `LearnedHashMap::polygon_search`.
+    ///
+    /// # Arguments
+    /// * `exterior` - The vertices of the polygon
+    #[inline]
+    pub fn polygon_search(&self, exterior: &[[F; 2]]) -> Option<Vec<V>> {
+        self.polygon_search_with_holes::<[[F; 2]; 0]>(exterior, &[])
+    }
+
+    /// Returns all the entries inside the given polygon and outside of its holes, see
+    /// `LearnedHashMap::polygon_search_with_holes`.
+    ///
+    /// # Arguments
+    /// * `exterior` - The vertices of the exterior of the polygon
+    /// * `holes` - The vertices of every hole of the polygon
+    pub fn polygon_search_with_holes<H>(&self, exterior: &[[F; 2]], holes: &[H]) -> Option<Vec<V>>
+    where
+        H: AsRef<[[F; 2]]>,
+    {
+        let result: Vec<V> = self
+            .query()
+            .polygon(exterior, holes)
+            .into_iter()
+            .cloned()
+            .collect();
+        if result.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> Buckets<F, D> for FrozenLearnedHashMap<M, F, V, D, N>
+where
+    F: Float,
+    M: Model<F = F> + Default,
+    V: PointLike<F, D>,
+{
+    type Model = M;
+    type Entry<'a>
+        = &'a V
+    where
+        Self: 'a;
+
+    #[inline]
+    fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    #[inline]
+    fn n_buckets(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    #[inline]
+    fn bucket(&self, index: usize) -> impl Iterator<Item = &V> {
+        self.entries[self.offsets[index]..self.offsets[index + 1]].iter()
+    }
+}
+
+impl<'a, M, F, V, const D: usize, const N: usize> IntoIterator
+    for &'a FrozenLearnedHashMap<M, F, V, D, N>
+{
+    type Item = &'a V;
+    type IntoIter = slice::Iter<'a, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Metric, PeriodicAxis, RetrainPolicy};
+    use crate::models::LinearModel;
+    use crate::test_utilities::*;
+
+    #[test]
+    fn freeze_and_thaw() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        map.insert(Point::new(0.5, 0.5)).unwrap();
+        let frozen = map.clone().freeze();
+
+        assert_eq!(map.len(), frozen.len());
+        assert_eq!(map.items(), frozen.items());
+        assert!(map.iter().eq(frozen.iter()));
+        for p in points.iter() {
+            assert_eq!(map.get(p), frozen.get(p));
+        }
+        assert_eq!(None, frozen.get(&[2., 2.]));
+
+        let (bottom_left, top_right) = ([0.2, 0.3], [0.5, 0.6]);
+        assert_eq!(
+            map.range_search(&bottom_left, &top_right),
+            frozen.range_search(&bottom_left, &top_right)
+        );
+        for sample_point in create_random_point_type_points(100, SEED_2).iter() {
+            assert_eq!(
+                map.radius_range_with_distance(sample_point, 0.1),
+                frozen.radius_range_with_distance(sample_point, 0.1)
+            );
+            assert_eq!(
+                map.k_nearest_neighbors(sample_point, 5),
+                frozen.k_nearest_neighbors(sample_point, 5)
+            );
+            assert_eq!(
+                map.k_nearest_neighbors_with::<Manhattan<f64>, _>(sample_point, 5),
+                frozen.k_nearest_neighbors_with::<Manhattan<f64>, _>(sample_point, 5)
+            );
+        }
+
+        let thawed = frozen.thaw();
+        assert_eq!(map.len(), thawed.len());
+        assert_eq!(map.items(), thawed.items());
+        assert!(map.iter().eq(thawed.iter()));
+    }
+
+    #[test]
+    fn thaw_keeps_config() {
+        let config = MapConfig {
+            load_factor: 2.,
+            growth_factor: 1.5,
+            initial_buckets: 64,
+            axis: Some(1),
+            retrain_policy: RetrainPolicy {
+                max_bucket_len: Some(16),
+                ..Default::default()
+            },
+            dedup_tolerance: Some(0.01),
+            metric: Metric::Euclidean,
+            periodic: Some(PeriodicAxis::new(0, 0., 1.)),
+        };
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64>, 2, 2>::builder()
+            .config(config)
+            .build()
+            .unwrap();
+        map.batch_insert(&mut create_random_point_type_points(100, SEED_1))
+            .unwrap();
+
+        let frozen = map.clone().freeze();
+        assert_eq!(&config, frozen.config());
+        let thawed: LearnedHashMap<LinearModel<f64>, f64, Point<f64>, 2, 2> = frozen.thaw();
+        assert_eq!(&config, thawed.config());
+        assert_eq!(map.len(), thawed.len());
+        assert!(map.iter().eq(thawed.iter()));
+    }
+
+    #[test]
+    fn freeze_empty() {
+        let map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        let frozen = map.freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(None, frozen.get(&[1., 1.]));
+        assert_eq!(None, frozen.nearest_neighbor(&[1., 1.]));
+        assert_eq!(None, frozen.range_search(&[0., 0.], &[1., 1.]));
+
+        let mut map = frozen.thaw();
+        map.insert(Point::new(1., 1.)).unwrap();
+        assert_eq!(Some(&Point::new(1., 1.)), map.get(&[1., 1.]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        type Frozen = FrozenLearnedHashMap<LinearModel<f64>, f64>;
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        let frozen = map.freeze();
+
+        let loaded: Frozen = bincode::deserialize(&bincode::serialize(&frozen).unwrap()).unwrap();
+        assert_eq!(frozen.len(), loaded.len());
+        assert_eq!(frozen.items(), loaded.items());
+        let query_point = [0.5, 0.5];
+        assert_eq!(
+            frozen.k_nearest_neighbors(&query_point, 5),
+            loaded.k_nearest_neighbors(&query_point, 5)
+        );
+
+        // Bucket offsets that do not match the entries are rejected
+        let n = points.len();
+        for offsets in [vec![], vec![1, n], vec![0, n + 1], vec![0, 10, 5, n]] {
+            let mut invalid = frozen.clone();
+            invalid.offsets = offsets;
+            let bytes = bincode::serialize(&invalid).unwrap();
+            assert!(bincode::deserialize::<Frozen>(&bytes).is_err());
+        }
+        // The hasher axis must be within the dimensions
+        let mut invalid = frozen.clone();
+        invalid.hasher.set_axis(2);
+        let bytes = bincode::serialize(&invalid).unwrap();
+        assert!(bincode::deserialize::<Frozen>(&bytes).is_err());
+    }
+}
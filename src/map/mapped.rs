@@ -0,0 +1,896 @@
+//! A flat binary file format of a LearnedHashMap, which can be memory-mapped and queried in
+//! place.
+//!
+//! All the values are stored in little endian, the file is laid out as
+//!
+//! ```text
+//! offset  size            content
+//! 0       4               magic b"LSPH"
+//! 4       4               format version, u32
+//! 8       4               size of the float type in bytes, u32
+//! 12      4               number of dimensions D, u32
+//! 16      4               hashed axis, u32
+//! 20      4               metric, u32, 0 for Euclidean and 1 for Haversine
+//! 24      4               model tag, u32, see below
+//! 28      4               periodic axis, u32, or u32::MAX without a periodic axis
+//! 32      8               lower bound of the periodic axis, f64
+//! 40      8               upper bound of the periodic axis, f64
+//! 48      8               number of buckets n, u64
+//! 56      8               number of points m, u64
+//! 64      8               number of model parameters p, u64
+//! 72      8 * p           model parameters, f64
+//! ..      8 * (n + 1)     bucket offsets into the point array, u64
+//! ..      size * D * m    point array, the points of bucket i are in offsets[i]..offsets[i + 1]
+//! ```
+//!
+//! The model tags and parameters are
+//!
+//! ```text
+//! tag     model           parameters
+//! 0       LinearModel     coefficient, intercept
+//! 1       PiecewiseModel  epsilon, then key, slope, intercept, lower, upper of every segment
+//! 2       SplineModel     maximum number of knots, then the keys, targets and tangents of the
+//!                         knots
+//! 3       RmiModel        number of leaves, size, coefficient and intercept of the root, then
+//!                         the segments of the leaves as in PiecewiseModel
+//! ```
+
+use crate::{
+    geometry::{Point, PointLike},
+    hasher::LearnedHasher,
+    map::{
+        query,
+        query::{Buckets, Query},
+        FrozenLearnedHashMap, LearnedHashMap, MapConfig, Metric, PeriodicAxis,
+    },
+    models::{LinearModel, Model},
+};
+use core::{fmt::Debug, iter::Sum, marker::PhantomData, mem, ops::Range};
+use memmap2::Mmap;
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Magic bytes at the start of the file
+const MAGIC: [u8; 4] = *b"LSPH";
+
+/// Version of the file format, bumped on every incompatible change of the layout
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Length of the header in bytes, before the model parameters
+const HEADER_LEN: usize = 72;
+
+/// Periodic axis in the header of a map without a periodic axis
+const NO_PERIODIC_AXIS: u32 = u32::MAX;
+
+/// Floating point types that can be stored in the flat file format
+pub trait FlatFloat: Float + FromPrimitive {
+    /// Reads the value from little endian bytes, the slice must hold exactly the size of Self
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    /// Appends the little endian bytes of the value
+    fn write_le(self, out: &mut impl Write) -> io::Result<()>;
+}
+
+impl FlatFloat for f32 {
+    #[inline]
+    fn from_le_slice(bytes: &[u8]) -> Self {
+        f32::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    #[inline]
+    fn write_le(self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+}
+
+impl FlatFloat for f64 {
+    #[inline]
+    fn from_le_slice(bytes: &[u8]) -> Self {
+        f64::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    #[inline]
+    fn write_le(self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+}
+
+/// Models that can be stored in the flat file format, as a tag and a list of parameters. The
+/// tags and the parameters of the models are listed in the description of the format.
+pub trait FlatModel: Model + Sized {
+    /// Returns the tag of the model
+    fn tag(&self) -> u32;
+
+    /// Returns the parameters of the model
+    fn params(&self) -> Vec<f64>;
+
+    /// Returns the model with the given tag and parameters, or None if they do not describe a
+    /// model of this type
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self>;
+}
+
+/// Tag of LinearModel
+pub(crate) const LINEAR_TAG: u32 = 0;
+
+/// Tag of PiecewiseModel
+pub(crate) const PIECEWISE_TAG: u32 = 1;
+
+/// Tag of SplineModel
+pub(crate) const SPLINE_TAG: u32 = 2;
+
+/// Tag of RmiModel
+pub(crate) const RMI_TAG: u32 = 3;
+
+/// Returns the parameters converted into the float type, or None if one is out of its range
+#[inline]
+pub(crate) fn params_into<F: FromPrimitive>(params: &[f64]) -> Option<Vec<F>> {
+    params.iter().map(|&param| F::from_f64(param)).collect()
+}
+
+/// Returns the parameter as a count, or None if it is not a non negative integer
+#[inline]
+pub(crate) fn param_count(param: f64) -> Option<usize> {
+    (param >= 0. && param.fract() == 0. && param <= u32::MAX as f64).then_some(param as usize)
+}
+
+/// Returns the value as a parameter, NaN if it cannot be represented, which is rejected when
+/// the map is written
+#[inline]
+pub(crate) fn param<F: Float>(value: F) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// Returns an InvalidData error with the given message
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads the little endian u32 at offset
+#[inline]
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads the little endian u64 at offset
+#[inline]
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Reads the little endian f64 at offset
+#[inline]
+fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Returns the code of the metric in the header
+#[inline]
+fn metric_code(metric: Metric) -> u32 {
+    match metric {
+        Metric::Euclidean => 0,
+        Metric::Haversine => 1,
+    }
+}
+
+/// Writes the buckets into a file in the flat format, with the metric and the periodic axis of
+/// the config
+fn write_buckets<B, F, P, const D: usize>(
+    buckets: &B,
+    items: usize,
+    config: &MapConfig,
+    path: P,
+) -> io::Result<()>
+where
+    B: Buckets<F, D>,
+    B::Model: FlatModel,
+    F: FlatFloat + Debug + Sum,
+    P: AsRef<Path>,
+{
+    let hasher = buckets.hasher();
+    let params = hasher.model.params();
+    if params.iter().any(|param| param.is_nan()) {
+        return Err(invalid_data("model parameters are not numbers"));
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+
+    out.write_all(&MAGIC)?;
+    out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    out.write_all(&(mem::size_of::<F>() as u32).to_le_bytes())?;
+    out.write_all(&(D as u32).to_le_bytes())?;
+    out.write_all(&(hasher.axis() as u32).to_le_bytes())?;
+    out.write_all(&metric_code(config.metric).to_le_bytes())?;
+    out.write_all(&hasher.model.tag().to_le_bytes())?;
+    let (periodic_axis, min, max) = match config.periodic_axis() {
+        Some(periodic) => (periodic.axis as u32, periodic.min, periodic.max),
+        None => (NO_PERIODIC_AXIS, 0., 0.),
+    };
+    out.write_all(&periodic_axis.to_le_bytes())?;
+    out.write_all(&min.to_le_bytes())?;
+    out.write_all(&max.to_le_bytes())?;
+    out.write_all(&(buckets.n_buckets() as u64).to_le_bytes())?;
+    out.write_all(&(items as u64).to_le_bytes())?;
+    out.write_all(&(params.len() as u64).to_le_bytes())?;
+    for param in params {
+        out.write_all(&param.to_le_bytes())?;
+    }
+
+    let mut offset = 0u64;
+    out.write_all(&offset.to_le_bytes())?;
+    for i in 0..buckets.n_buckets() {
+        offset += buckets.bucket(i).count() as u64;
+        out.write_all(&offset.to_le_bytes())?;
+    }
+    for i in 0..buckets.n_buckets() {
+        for e in buckets.bucket(i) {
+            for c in e.coords() {
+                c.write_le(&mut out)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMap<M, F, V, D, N>
+where
+    F: FlatFloat + Default + AsPrimitive<u64> + Debug + Sum,
+    M: FlatModel<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Writes the map into a file in the flat format, which can be opened by
+    /// MappedLearnedHashMap with the same model type. The model, the coordinates of the
+    /// entries, the metric and the periodic axis are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written, or an `InvalidData` error if a parameter
+    /// of the model is NaN.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file, the file is created or truncated
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, MappedLearnedHashMap, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, _) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// let path = std::env::temp_dir().join("lsph_write_to_example.bin");
+    /// map.write_to(&path).unwrap();
+    ///
+    /// let mapped = MappedLearnedHashMap::<f64>::open(&path).unwrap();
+    /// assert_eq!(mapped.get(&[2., 1.]), Some(Point::new(2., 1.)));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    #[inline]
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if self.migration.is_some() {
+            // The file holds a single table, so the entries being migrated are moved first
+            let mut map = self.clone();
+            map.finish_migration();
+            return map.write_to(path);
+        }
+        write_buckets(self, self.items(), &self.config, path)
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> FrozenLearnedHashMap<M, F, V, D, N>
+where
+    F: FlatFloat + Default + AsPrimitive<u64> + Debug + Sum,
+    M: FlatModel<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Writes the map into a file in the flat format, see `LearnedHashMap::write_to`.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file, the file is created or truncated
+    #[inline]
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_buckets(self, self.items(), self.config(), path)
+    }
+}
+
+/// MappedLearnedHashMap is a read-only LearnedHashMap backed by a memory-mapped file written by
+/// `LearnedHashMap::write_to`.
+///
+/// The points are read from the mapped file in place, so opening a map is cheap, and many
+/// processes can share the pages of the same index file. The model type M must be the model
+/// type of the written map, or `AutoModel` which opens the files of every model.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, MappedLearnedHashMap};
+/// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+/// let (map, _) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+///
+/// let path = std::env::temp_dir().join("lsph_mapped_example.bin");
+/// map.write_to(&path).unwrap();
+///
+/// let mapped = MappedLearnedHashMap::<f64>::open(&path).unwrap();
+/// assert_eq!(mapped.items(), 4);
+/// assert_eq!(mapped.range_search(&[0., 0.], &[3., 3.]).unwrap().len(), 3);
+/// assert_eq!(mapped.nearest_neighbor(&[2.9, 2.]).unwrap().coords(), &[3., 2.]);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MappedLearnedHashMap<F, const D: usize = 2, M = LinearModel<F>> {
+    hasher: LearnedHasher<M>,
+    mmap: Mmap,
+    /// Offset of the bucket offsets in the file
+    offsets_start: usize,
+    n_buckets: usize,
+    n_points: usize,
+    metric: Metric,
+    periodic: Option<PeriodicAxis>,
+    _marker: PhantomData<F>,
+}
+
+impl<F, const D: usize, M> MappedLearnedHashMap<F, D, M>
+where
+    F: FlatFloat + Default + AsPrimitive<u64> + Debug + Sum,
+    M: FlatModel<F = F> + Default,
+{
+    /// Opens the file written by `LearnedHashMap::write_to` with the same float type, dimensions
+    /// and model type.
+    ///
+    /// The file must not be modified while it is mapped, it is read in place by the queries.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or an `InvalidData` error if the file is not
+    /// a valid map file of the float type, dimensions and model type.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read only, and the file is documented to stay unchanged while it is
+        // mapped. All the reads are bounds checked against the validated layout.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let bytes = &mmap[..];
+
+        if bytes.len() < HEADER_LEN || bytes[0..4] != MAGIC {
+            return Err(invalid_data("not a learned hash map file"));
+        }
+        if read_u32(bytes, 4) != FORMAT_VERSION {
+            return Err(invalid_data("unsupported format version"));
+        }
+        if read_u32(bytes, 8) as usize != mem::size_of::<F>() {
+            return Err(invalid_data("float type does not match"));
+        }
+        if read_u32(bytes, 12) as usize != D {
+            return Err(invalid_data("dimensions do not match"));
+        }
+        let axis = read_u32(bytes, 16) as usize;
+        if axis >= D {
+            return Err(invalid_data("axis out of dimensions"));
+        }
+        let metric = match read_u32(bytes, 20) {
+            0 => Metric::Euclidean,
+            1 if D == 2 => Metric::Haversine,
+            _ => return Err(invalid_data("unsupported metric")),
+        };
+        let periodic = match read_u32(bytes, 28) {
+            NO_PERIODIC_AXIS => None,
+            axis => {
+                let periodic =
+                    PeriodicAxis::new(axis as usize, read_f64(bytes, 32), read_f64(bytes, 40));
+                let config = MapConfig {
+                    periodic: Some(periodic),
+                    ..Default::default()
+                };
+                if config.validate::<D>().is_err() {
+                    return Err(invalid_data("invalid periodic axis"));
+                }
+                Some(periodic)
+            }
+        };
+
+        let n_buckets = usize::try_from(read_u64(bytes, 48));
+        let n_points = usize::try_from(read_u64(bytes, 56));
+        let n_params = usize::try_from(read_u64(bytes, 64));
+        let (n_buckets, n_points, n_params) = match (n_buckets, n_points, n_params) {
+            (Ok(n_buckets), Ok(n_points), Ok(n_params)) => (n_buckets, n_points, n_params),
+            _ => return Err(invalid_data("table is too large")),
+        };
+        let offsets_start = n_params
+            .checked_mul(8)
+            .and_then(|params| params.checked_add(HEADER_LEN));
+        let expected_len = offsets_start.and_then(|offsets_start| {
+            let offsets = n_buckets.checked_add(1)?.checked_mul(8)?;
+            let points = n_points.checked_mul(D)?.checked_mul(mem::size_of::<F>())?;
+            offsets_start.checked_add(offsets)?.checked_add(points)
+        });
+        let offsets_start = match offsets_start {
+            Some(offsets_start) if expected_len == Some(bytes.len()) => offsets_start,
+            _ => return Err(invalid_data("file length does not match the header")),
+        };
+
+        // Offsets must be increasing from 0 to the number of points
+        let mut prev = 0u64;
+        for i in 0..=n_buckets {
+            let offset = read_u64(bytes, offsets_start + 8 * i);
+            if offset < prev || (i == 0 && offset != 0) {
+                return Err(invalid_data("bucket offsets are not increasing"));
+            }
+            prev = offset;
+        }
+        if prev != n_points as u64 {
+            return Err(invalid_data(
+                "bucket offsets do not match the number of points",
+            ));
+        }
+
+        let params: Vec<f64> = (0..n_params)
+            .map(|i| read_f64(bytes, HEADER_LEN + 8 * i))
+            .collect();
+        let model = match M::from_params(read_u32(bytes, 24), &params) {
+            Some(model) => model,
+            None => return Err(invalid_data("model does not match")),
+        };
+        let mut hasher = LearnedHasher::with_model(model);
+        hasher.set_axis(axis);
+
+        Ok(Self {
+            hasher,
+            mmap,
+            offsets_start,
+            n_buckets,
+            n_points,
+            metric,
+            periodic,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the number of buckets.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.n_buckets
+    }
+
+    /// Returns the number of points.
+    #[inline]
+    pub fn items(&self) -> usize {
+        self.n_points
+    }
+
+    /// Returns the metric of the nearest neighbor queries, see `MapConfig::metric`.
+    #[inline]
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Returns the periodic axis, see `MapConfig::periodic_axis`.
+    #[inline]
+    pub fn periodic(&self) -> Option<PeriodicAxis> {
+        self.periodic
+    }
+
+    /// Returns the hasher of the map.
+    #[inline]
+    pub fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    /// Returns the front end of the queries over the buckets.
+    #[inline]
+    fn query(&self) -> Query<'_, F, Self, Self, D> {
+        Query::new(self, None, self.metric, self.periodic)
+    }
+
+    /// Returns bool if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.n_points == 0
+    }
+
+    /// Returns the range of the points of the bucket in the point array
+    #[inline]
+    fn bucket_range(&self, index: usize) -> Range<usize> {
+        let offset = self.offsets_start + 8 * index;
+        read_u64(&self.mmap, offset) as usize..read_u64(&self.mmap, offset + 8) as usize
+    }
+
+    /// Returns the point at index of the point array
+    #[inline]
+    fn point(&self, index: usize) -> Point<F, D> {
+        let size = mem::size_of::<F>();
+        let start = self.offsets_start + 8 * (self.n_buckets + 1) + index * D * size;
+        let bytes = &self.mmap[start..start + D * size];
+        Point::from_coords(core::array::from_fn(|i| {
+            F::from_le_slice(&bytes[i * size..(i + 1) * size])
+        }))
+    }
+
+    /// Returns Option<Point<F, D>> with given point data.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    #[inline]
+    pub fn get<P: PointLike<F, D>>(&self, p: &P) -> Option<Point<F, D>> {
+        query::find(self, &p.coords())
+    }
+
+    /// Returns bool if the map contains the point.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    #[inline]
+    pub fn contains_points<P: PointLike<F, D>>(&self, p: &P) -> bool {
+        self.get(p).is_some()
+    }
+
+    /// Returns all the points within the given range, see `LearnedHashMap::range_search`.
+    ///
+    /// # Arguments
+    /// * `bottom_left` - A point-like data that represents the bottom left of the range.
+    /// * `top_right` - A point-like data that represents the top right of the range.
+    pub fn range_search<P: PointLike<F, D>>(
+        &self,
+        bottom_left: &P,
+        top_right: &P,
+    ) -> Option<Vec<Point<F, D>>> {
+        let result = self
+            .query()
+            .range(&bottom_left.coords(), &top_right.coords());
+        if result.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Returns the closest point to the query point, see `LearnedHashMap::nearest_neighbor`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    #[inline]
+    pub fn nearest_neighbor<P: PointLike<F, D>>(&self, query_point: &P) -> Option<Point<F, D>> {
+        self.k_nearest_neighbors(query_point, 1).pop()
+    }
+
+    /// Returns the k closest points to the query point ordered by distance, see
+    /// `LearnedHashMap::k_nearest_neighbors`.
+    ///
+    /// # Arguments
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    #[inline]
+    pub fn k_nearest_neighbors<P: PointLike<F, D>>(
+        &self,
+        query_point: &P,
+        k: usize,
+    ) -> Vec<Point<F, D>> {
+        self.query().nearest(&query_point.coords(), k, |_| true)
+    }
+}
+
+impl<F, const D: usize, M> Buckets<F, D> for MappedLearnedHashMap<F, D, M>
+where
+    F: FlatFloat + Default + AsPrimitive<u64> + Debug + Sum,
+    M: FlatModel<F = F> + Default,
+{
+    type Model = M;
+    type Entry<'a>
+        = Point<F, D>
+    where
+        Self: 'a;
+
+    #[inline]
+    fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    #[inline]
+    fn n_buckets(&self) -> usize {
+        self.n_buckets
+    }
+
+    #[inline]
+    fn bucket(&self, index: usize) -> impl Iterator<Item = Point<F, D>> {
+        self.bucket_range(index).map(|i| self.point(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{AutoModel, PiecewiseModel, RmiModel, SplineModel},
+        test_utilities::*,
+    };
+    use std::path::PathBuf;
+
+    /// Returns a path in the temp dir unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lsph_{}_{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn write_and_open() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        let path = temp_path("write_and_open");
+        map.write_to(&path).unwrap();
+        let mapped = MappedLearnedHashMap::<f64>::open(&path).unwrap();
+        let frozen_path = temp_path("write_and_open_frozen");
+        map.clone().freeze().write_to(&frozen_path).unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            std::fs::read(&frozen_path).unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&frozen_path).unwrap();
+
+        assert_eq!(map.len(), mapped.len());
+        assert_eq!(map.items(), mapped.items());
+        for p in points.iter() {
+            assert_eq!(Some(*p), mapped.get(p));
+        }
+        assert_eq!(None, mapped.get(&[2., 2.]));
+
+        let (bottom_left, top_right) = ([0.2, 0.3], [0.5, 0.6]);
+        assert_eq!(
+            map.range_search(&bottom_left, &top_right),
+            mapped.range_search(&bottom_left, &top_right)
+        );
+        for sample_point in create_random_point_type_points(100, SEED_2).iter() {
+            assert_eq!(
+                map.nearest_neighbor(sample_point),
+                mapped.nearest_neighbor(sample_point)
+            );
+            assert_eq!(
+                map.k_nearest_neighbors(sample_point, 5),
+                mapped.k_nearest_neighbors(sample_point, 5)
+            );
+        }
+    }
+
+    #[test]
+    fn write_and_open_3d_f32() {
+        let mut points: Vec<Point<f32, 3>> = create_random_nd_points::<3>(500, SEED_1)
+            .iter()
+            .map(|p| Point::from_coords(p.coords().map(|c| c as f32)))
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f32>, f32, Point<f32, 3>, 3>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        let path = temp_path("write_and_open_3d_f32");
+        map.write_to(&path).unwrap();
+        let mapped = MappedLearnedHashMap::<f32, 3>::open(&path).unwrap();
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            MappedLearnedHashMap::<f64, 3>::open(&path)
+                .err()
+                .unwrap()
+                .kind()
+        );
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            MappedLearnedHashMap::<f32, 2>::open(&path)
+                .err()
+                .unwrap()
+                .kind()
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        for p in points.iter() {
+            assert_eq!(Some(*p), mapped.get(p));
+        }
+        let query_point = [0.5, 0.5, 0.5];
+        assert_eq!(
+            map.k_nearest_neighbors(&query_point, 3),
+            mapped.k_nearest_neighbors(&query_point, 3)
+        );
+    }
+
+    #[test]
+    fn write_and_open_geographic() {
+        let mut points: Vec<Point<f64>> = create_random_point_type_points(1000, SEED_1)
+            .iter()
+            .map(|p| Point::new(p.x() * 0.2 - 37.9, p.y() * 0.3 + 144.8))
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .metric(Metric::Haversine)
+            .build()
+            .unwrap();
+        map.batch_insert(&mut points).unwrap();
+
+        let path = temp_path("write_and_open_geographic");
+        map.write_to(&path).unwrap();
+        let mapped = MappedLearnedHashMap::<f64>::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Metric::Haversine, mapped.metric());
+        let query_point = [-37.8136, 144.9631];
+        assert_eq!(
+            map.k_nearest_neighbors(&query_point, 5),
+            mapped.k_nearest_neighbors(&query_point, 5)
+        );
+    }
+
+    #[test]
+    fn open_invalid_files() {
+        let mut points = create_random_point_type_points(100, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        let path = temp_path("open_invalid_files");
+        map.write_to(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        // Truncated file
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Wrong magic
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Unknown metric
+        let mut corrupted = bytes.clone();
+        corrupted[20] = 2;
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Decreasing bucket offsets, after the 2 parameters of the linear model
+        let offsets_start = HEADER_LEN + 8 * 2;
+        let mut corrupted = bytes.clone();
+        corrupted[offsets_start + 8..offsets_start + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Unknown model tag
+        let mut corrupted = bytes.clone();
+        corrupted[24] = 9;
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64, 2, AutoModel<f64>>::open(&path)
+            .err()
+            .unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Wrong number of model parameters
+        let mut corrupted = bytes.clone();
+        corrupted[64..72].copy_from_slice(&3u64.to_le_bytes());
+        corrupted.splice(offsets_start..offsets_start, 0f64.to_le_bytes());
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        // Periodic axis out of dimensions
+        let mut corrupted = bytes.clone();
+        corrupted[28..32].copy_from_slice(&2u32.to_le_bytes());
+        std::fs::write(&path, &corrupted).unwrap();
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_and_open_models() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let sample_points = create_random_point_type_points(50, SEED_2);
+
+        fn check<M: FlatModel<F = f64> + Default + Clone>(
+            points: &mut [Point<f64>],
+            sample_points: &[Point<f64>],
+            name: &str,
+        ) -> PathBuf {
+            let mut map = LearnedHashMap::<M, f64>::new();
+            map.batch_insert(points).unwrap();
+            let path = temp_path(name);
+            map.write_to(&path).unwrap();
+
+            let mapped = MappedLearnedHashMap::<f64, 2, M>::open(&path).unwrap();
+            let auto = MappedLearnedHashMap::<f64, 2, AutoModel<f64>>::open(&path).unwrap();
+            assert_eq!(map.hasher().model.params(), mapped.hasher().model.params());
+            assert_eq!(map.hasher().model.params(), auto.hasher().model.params());
+            for p in points.iter() {
+                assert_eq!(Some(*p), mapped.get(p));
+                assert_eq!(Some(*p), auto.get(p));
+            }
+            for sample_point in sample_points.iter() {
+                assert_eq!(
+                    map.k_nearest_neighbors(sample_point, 5),
+                    mapped.k_nearest_neighbors(sample_point, 5)
+                );
+            }
+            path
+        }
+
+        let path = check::<PiecewiseModel<f64>>(&mut points, &sample_points, "models_piecewise");
+        // A file of another model does not open as a linear map
+        let err = MappedLearnedHashMap::<f64>::open(&path).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        std::fs::remove_file(&path).unwrap();
+        for path in [
+            check::<SplineModel<f64>>(&mut points, &sample_points, "models_spline"),
+            check::<RmiModel<f64>>(&mut points, &sample_points, "models_rmi"),
+            check::<AutoModel<f64>>(&mut points, &sample_points, "models_auto"),
+        ] {
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn open_invalid_model_params() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let path = temp_path("open_invalid_model_params");
+        let param_offset = |i: usize| HEADER_LEN + 8 * i;
+        let open_corrupted = |bytes: &[u8], i: usize, value: f64| {
+            let mut corrupted = bytes.to_vec();
+            corrupted[param_offset(i)..param_offset(i) + 8].copy_from_slice(&value.to_le_bytes());
+            std::fs::write(&path, &corrupted).unwrap();
+            MappedLearnedHashMap::<f64, 2, AutoModel<f64>>::open(&path)
+        };
+
+        // The number of leaves does not match the leaf segments
+        let mut map = LearnedHashMap::<RmiModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        map.write_to(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let n_leaves = read_f64(&bytes, param_offset(0));
+        assert!(open_corrupted(&bytes, 0, n_leaves).is_ok());
+        for n_leaves in [n_leaves - 1., n_leaves + 1.] {
+            let err = open_corrupted(&bytes, 0, n_leaves).err().unwrap();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+
+        // The keys of the knots are not increasing, or not finite
+        let mut map = LearnedHashMap::<SplineModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        map.write_to(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let first_key = read_f64(&bytes, param_offset(1));
+        for key in [first_key, f64::INFINITY] {
+            let err = open_corrupted(&bytes, 2, key).err().unwrap();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_and_open_periodic() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .periodic(PeriodicAxis::new(0, 0., 1.))
+            .build()
+            .unwrap();
+        map.batch_insert(&mut points).unwrap();
+
+        let path = temp_path("write_and_open_periodic");
+        map.write_to(&path).unwrap();
+        let mapped = MappedLearnedHashMap::<f64>::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(map.config().periodic_axis(), mapped.periodic());
+        // The range from 0.9 to 0.1 crosses the boundary
+        assert_eq!(
+            map.range_search(&[0.9, 0.], &[0.1, 0.5]),
+            mapped.range_search(&[0.9, 0.], &[0.1, 0.5])
+        );
+        for query_point in [[0.01, 0.5], [0.99, 0.3], [1.5, 0.7]] {
+            assert_eq!(
+                map.k_nearest_neighbors(&query_point, 10),
+                mapped.k_nearest_neighbors(&query_point, 10)
+            );
+        }
+    }
+}
@@ -1,36 +1,148 @@
+mod config;
+mod frozen;
+#[cfg(feature = "mmap")]
+mod mapped;
 mod nn;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod policy;
+mod query;
+mod resize;
+mod stats;
 mod table;
 
+pub use config::*;
+pub use frozen::*;
+#[cfg(feature = "mmap")]
+pub use mapped::*;
+pub use policy::*;
+pub use stats::*;
+
 use crate::{
     error::*,
-    geometry::{distance::*, Point},
+    geometry::{distance::*, Point, PointLike},
     hasher::*,
-    map::{nn::*, table::*},
-    models::Model,
+    map::{
+        query::{Buckets, Query},
+        resize::*,
+        table::*,
+    },
+    models::{Model, Trainer},
 };
-use core::{fmt::Debug, iter::Sum, mem};
+use core::{array, fmt::Debug, iter::Sum, marker::PhantomData, mem};
 use num_traits::{
     cast::{AsPrimitive, FromPrimitive},
     float::Float,
 };
-use std::collections::BinaryHeap;
-
-/// Initial bucket size is set to 1
-const INITIAL_NBUCKETS: usize = 1;
 
 /// LearnedHashMap takes a model instead of an hasher for hashing indexes in the table.
 ///
 /// Default Model for the LearndedHashMap is Linear regression.
 /// In order to build a ordered HashMap, we need to make sure that the model is **monotonic**.
+///
+/// The map stores entries of type `V`, which defaults to a bare `Point<F>`. Use a
+/// `(Point<F>, T)` pair as entry type to store a payload alongside each point.
+///
+/// Points are 2d by default, the const generic `D` sets the number of dimensions of the points,
+/// e.g. `LearnedHashMap<M, F, Point<F, 3>, 3>` for 3d points. The model is trained along the axis
+/// with the largest variance.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, Point};
+/// let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, &str)>::new();
+/// map.insert((Point::new(1., 1.), "a")).unwrap();
+/// map.insert((Point::new(2., 1.), "b")).unwrap();
+///
+/// assert_eq!(map.get(&[2., 1.]).unwrap().1, "b");
+/// ```
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, Point};
+/// let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64, 3>, 3>::new();
+/// let mut points = vec![
+///     Point::from_coords([1., 1., 1.]),
+///     Point::from_coords([2., 1., 3.]),
+///     Point::from_coords([3., 2., 6.]),
+/// ];
+/// map.batch_insert(&mut points).unwrap();
+///
+/// assert!(map.get(&[2., 1., 3.]).is_some());
+/// ```
 #[derive(Debug, Clone)]
-pub struct LearnedHashMap<M, F> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "MapParts<M, V, N>",
+        bound(deserialize = "M: Model<F = F> + serde::Deserialize<'de>, \
+                             F: Float, V: serde::Deserialize<'de>")
+    )
+)]
+pub struct LearnedHashMap<M, F, V = Point<F>, const D: usize = 2, const N: usize = 6> {
+    hasher: LearnedHasher<M>,
+    table: Table<V, N>,
+    items: usize,
+    migration: Option<Migration<M, V, N>>,
+    config: MapConfig,
+    /// Number of inserts since the model was last trained
+    inserts: usize,
+    /// Sum of the squared lengths of the buckets of the table, which is the number of entries
+    /// scanned by looking up every entry of the table
+    probes: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _marker: PhantomData<F>,
+}
+
+/// The fields of a deserialized LearnedHashMap, before they are validated
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct MapParts<M, V, const N: usize> {
     hasher: LearnedHasher<M>,
-    table: Table<Point<F>>,
+    table: Table<V, N>,
     items: usize,
+    migration: Option<Migration<M, V, N>>,
+    config: MapConfig,
+    inserts: usize,
+    probes: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<M, F, V, const D: usize, const N: usize> TryFrom<MapParts<M, V, N>>
+    for LearnedHashMap<M, F, V, D, N>
+where
+    F: Float,
+    M: Model<F = F>,
+{
+    type Error = &'static str;
+
+    /// Checks that the configuration and the axes of the hashers are valid for the dimensions,
+    /// so that a map rejected by `with_config` cannot be loaded either.
+    fn try_from(parts: MapParts<M, V, N>) -> Result<Self, Self::Error> {
+        let axis_in_range = parts.hasher.axis() < D
+            && parts
+                .migration
+                .as_ref()
+                .is_none_or(|migration| migration.hasher.axis() < D);
+        if parts.config.validate::<D>().is_err() || !axis_in_range {
+            return Err("map configuration is out of range");
+        }
+        Ok(LearnedHashMap {
+            hasher: parts.hasher,
+            table: parts.table,
+            items: parts.items,
+            migration: parts.migration,
+            config: parts.config,
+            inserts: parts.inserts,
+            probes: parts.probes,
+            _marker: PhantomData,
+        })
+    }
 }
 
 /// Default for the LearndedHashMap.
-impl<M, F> Default for LearnedHashMap<M, F>
+impl<M, F, V, const D: usize, const N: usize> Default for LearnedHashMap<M, F, V, D, N>
 where
     F: Float,
     M: Model<F = F> + Default,
@@ -40,14 +152,20 @@ where
             hasher: LearnedHasher::<M>::new(),
             table: Table::new(),
             items: 0,
+            migration: None,
+            config: MapConfig::default(),
+            inserts: 0,
+            probes: 0,
+            _marker: PhantomData,
         }
     }
 }
 
-impl<M, F> LearnedHashMap<M, F>
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMap<M, F, V, D, N>
 where
     F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
     M: Model<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
 {
     /// Returns a default LearnedHashMap with Model and Float type.
     ///
@@ -79,6 +197,11 @@ where
             hasher,
             table: Table::new(),
             items: 0,
+            migration: None,
+            config: MapConfig::default(),
+            inserts: 0,
+            probes: 0,
+            _marker: PhantomData,
         }
     }
 
@@ -99,101 +222,164 @@ where
             hasher: Default::default(),
             table: Table::with_capacity(capacity),
             items: 0,
+            migration: None,
+            config: MapConfig::default(),
+            inserts: 0,
+            probes: 0,
+            _marker: PhantomData,
         }
     }
 
-    /// Returns a default LearnedHashMap with Model and Float type
+    /// Returns an empty LearnedHashMap with the given configuration.
     ///
     /// # Arguments
-    /// * `data` - A Vec<[F; 2]> of 2d points for the map
+    /// * `config` - A MapConfig
+    ///
+    /// # Errors
+    ///
+    /// Returns Error::InvalidConfig if the configuration is out of range, see
+    /// `MapConfig::validate`.
     ///
     /// # Examples
     ///
     /// ```
-    /// use lsph::{LearnedHashMap, LinearModel};
-    /// let data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let map = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data);
+    /// use lsph::{LearnedHashMap, LinearModel, MapConfig};
+    /// let config = MapConfig {
+    ///     load_factor: 1.5,
+    ///     ..Default::default()
+    /// };
+    /// let map = LearnedHashMap::<LinearModel<f64>, f64>::with_config(config).unwrap();
     /// ```
     #[inline]
-    pub fn with_data(data: &[[F; 2]]) -> Result<(Self, Vec<Point<F>>), Error> {
-        use crate::helper::convert_to_points;
-        let mut map = LearnedHashMap::with_capacity(data.len());
-        let mut ps = convert_to_points(data).unwrap();
-        match map.batch_insert(&mut ps) {
-            Ok(()) => Ok((map, ps)),
-            Err(err) => Err(err),
+    pub fn with_config(config: MapConfig) -> Result<Self, Error> {
+        config.validate::<D>()?;
+        let mut hasher = LearnedHasher::new();
+        if let Some(axis) = config.axis {
+            hasher.set_axis(axis);
         }
+        Ok(Self {
+            hasher,
+            config,
+            ..Self::default()
+        })
+    }
+
+    /// Returns a builder for a LearnedHashMap with a custom hasher and configuration, see
+    /// `LearnedHashMapBuilder`.
+    #[inline]
+    pub fn builder() -> LearnedHashMapBuilder<M, F, V, D, N> {
+        LearnedHashMapBuilder::new()
     }
 
-    /// Returns Option<Point<F>>  with given point data.
+    /// Returns the configuration of the map.
+    #[inline]
+    pub fn config(&self) -> &MapConfig {
+        &self.config
+    }
+
+    /// Returns Option<&V> with given point data.
     ///
     /// # Arguments
-    /// * `p` - A array slice containing two points for querying
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
     ///
     /// # Examples
     ///
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.get(&[1., 1.]).is_some(), true);
     /// ```
     #[inline]
-    pub fn get(&mut self, p: &[F; 2]) -> Option<&Point<F>> {
-        let hash = make_hash_point(&mut self.hasher, p) as usize;
-        if hash > self.table.capacity() {
-            return None;
+    pub fn get<P: PointLike<F, D>>(&self, p: &P) -> Option<&V> {
+        let p = p.coords();
+        query::find(self, &p).or_else(|| query::find(self.migration.as_ref()?, &p))
+    }
+
+    /// Returns Option<&V> of the closest entry within epsilon of the given point, measured by
+    /// Euclidean distance. Use it to find points whose coordinates went through a lossy
+    /// conversion, e.g. a lat/lng round-trip.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    /// * `epsilon` - The largest distance to the point
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// assert!(map.get(&[2. + 1e-9, 1.]).is_none());
+    /// assert_eq!(map.get_approx(&[2. + 1e-9, 1.], 1e-6), Some(&Point::new(2., 1.)));
+    /// ```
+    #[inline]
+    pub fn get_approx<P: PointLike<F, D>>(&self, p: &P, epsilon: F) -> Option<&V> {
+        self.find_approx(&p.coords(), epsilon).map(|(e, _)| e)
+    }
+
+    /// Returns the closest entry within epsilon of the point in the table and in the table
+    /// being migrated, along with its distance to the point.
+    #[inline]
+    fn find_approx(&self, p: &[F; D], epsilon: F) -> Option<(&V, F)> {
+        let found = query::find_approx(self, p, epsilon);
+        let migrating = self
+            .migration
+            .as_ref()
+            .and_then(|migration| query::find_approx(migration, p, epsilon));
+        match (found, migrating) {
+            (Some(a), Some(b)) if b.1 < a.1 => Some(b),
+            (found, migrating) => found.or(migrating),
         }
-        self.find_by_hash(hash, p)
     }
 
-    /// Returns Option<Point<F>> by hash index, if it exists in the map.
+    /// Returns Option<&V> by hash index, if it exists in the map.
     ///
     /// # Arguments
     /// * `hash` - An usize hash value
-    /// * `p` - A array slice containing two points for querying
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
     ///
     /// # Examples
     ///
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.find_by_hash(0, &[1., 1.]).is_some(), true);
     /// assert_eq!(map.find_by_hash(1, &[1., 1.]).is_none(), true);
     /// ```
-    pub fn find_by_hash(&self, hash: usize, p: &[F; 2]) -> Option<&Point<F>> {
-        self.table[hash]
-            .iter()
-            .find(|&ep| ep.x == p[0] && ep.y == p[1])
+    pub fn find_by_hash<P: PointLike<F, D>>(&self, hash: usize, p: &P) -> Option<&V> {
+        let p = p.coords();
+        self.table.get(hash)?.iter().find(|&e| e.coords() == p)
     }
 
     /// Returns bool.
     ///
     /// # Arguments
-    /// * `p` - A array slice containing two points for querying
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
     ///
     /// # Examples
     ///
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.contains_points(&[1., 1.]), true);
     /// assert_eq!(map.contains_points(&[0., 1.]), false);
     /// ```
     #[inline]
-    pub fn contains_points(&mut self, p: &[F; 2]) -> bool {
+    pub fn contains_points<P: PointLike<F, D>>(&self, p: &P) -> bool {
         self.get(p).is_some()
     }
 
-    /// Returns Option<Point<F>> if the map contains a point and successful remove it from the map.
+    /// Returns Option<V> if the map contains a point and successful remove it from the map.
     ///
     /// # Arguments
-    /// * `p` - A Point data
+    /// * `p` - A point-like data
     ///
     /// # Examples
     ///
@@ -204,12 +390,49 @@ where
     ///
     /// let p = points[0];
     /// assert_eq!(map.remove(&p).unwrap(), p);
+    /// assert_eq!(map.remove(&p), None);
     /// ```
     #[inline]
-    pub fn remove(&mut self, p: &Point<F>) -> Option<Point<F>> {
-        let hash = make_hash_point(&mut self.hasher, &[p.x, p.y]);
+    pub fn remove<P: PointLike<F, D>>(&mut self, p: &P) -> Option<V> {
+        let p = p.coords();
+        self.migrate(MIGRATION_STEP);
+        let hash = make_hash_point(&self.hasher, &p);
+        let e = if hash < self.table.len() as u64 {
+            let e = self.table.remove_entry(hash, |e| e.coords() == p);
+            if e.is_some() {
+                self.probes -= 2 * self.table[self.table.bucket(hash)].len() + 1;
+            }
+            e
+        } else {
+            None
+        };
+        let e = e.or_else(|| self.migration.as_mut()?.remove(&p))?;
         self.items -= 1;
-        self.table.remove_entry(hash, *p)
+        Some(e)
+    }
+
+    /// Returns Option<V> if the map contains an entry within epsilon of the given point, and
+    /// successful remove the closest such entry from the map.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data
+    /// * `epsilon` - The largest distance to the point
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// assert_eq!(map.remove_approx(&[3., 2.0001], 0.001), Some(Point::new(3., 2.)));
+    /// assert_eq!(map.remove_approx(&[3., 2.0001], 0.001), None);
+    /// ```
+    #[inline]
+    pub fn remove_approx<P: PointLike<F, D>>(&mut self, p: &P, epsilon: F) -> Option<V> {
+        let (e, _) = self.find_approx(&p.coords(), epsilon)?;
+        let p = e.coords();
+        self.remove(&p)
     }
 
     /// Returns usize length.
@@ -219,7 +442,7 @@ where
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.len(), 4);
     /// ```
@@ -235,7 +458,7 @@ where
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.items(), 4);
     /// ```
@@ -251,7 +474,7 @@ where
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.is_empty(), false);
     /// ```
@@ -260,54 +483,278 @@ where
         self.items == 0
     }
 
-    /// Resize the map if needed, it will initialize the map to the INITIAL_NBUCKETS, otherwise it will double the capacity if table is not empty.
-    fn resize(&mut self) {
-        let target_size = match self.table.len() {
-            0 => INITIAL_NBUCKETS,
-            n => 2 * n,
-        };
-        self.resize_with_capacity(target_size);
+    /// Returns an iterator over the entries, in bucket order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, M, F, V, D, N> {
+        self.into_iter()
     }
 
-    /// Resize the map if needed, it will resize the map to desired capacity.
+    /// Returns the retrain policy of the map.
+    #[inline]
+    pub fn retrain_policy(&self) -> &RetrainPolicy {
+        &self.config.retrain_policy
+    }
+
+    /// Sets the retrain policy of the map, which is checked after every insert.
+    ///
+    /// # Arguments
+    /// * `policy` - A RetrainPolicy
+    #[inline]
+    pub fn set_retrain_policy(&mut self, policy: RetrainPolicy) {
+        self.config.retrain_policy = policy;
+    }
+
+    /// Returns true if the point is in the domain of the periodic axis, or if the point is not
+    /// finite, which is rejected by the finite checks instead.
+    #[inline]
+    fn in_domain(&self, p: &[F; D]) -> bool {
+        self.config.periodic_axis().is_none_or(|periodic| {
+            let x = p[periodic.axis];
+            !x.is_finite() || x.to_f64().is_some_and(|x| periodic.contains(x))
+        })
+    }
+
+    /// Returns the front end of the queries, over the table and the table being migrated.
     #[inline]
-    fn resize_with_capacity(&mut self, target_size: usize) {
-        let mut new_table = Table::with_capacity(target_size);
-        new_table.extend((0..target_size).map(|_| Bucket::new()));
+    fn query(&self) -> Query<'_, F, Self, Migration<M, V, N>, D> {
+        Query::new(
+            self,
+            self.migration.as_ref(),
+            self.config.metric,
+            self.config.periodic_axis(),
+        )
+    }
 
-        for p in self.table.iter_mut().flat_map(|bucket| bucket.drain(..)) {
-            let hash = make_hash_point(&mut self.hasher, &[p.x, p.y]) as usize;
-            new_table[hash].push(p);
+    /// Returns the mean probe length of the map, that is the mean length of the bucket of an
+    /// entry, or 0 if the map is empty. Entries of a table being migrated are not counted.
+    #[inline]
+    pub fn mean_probe_len(&self) -> f64 {
+        let items = self.items - self.migration.as_ref().map_or(0, |m| m.table.items());
+        if items == 0 {
+            return 0.;
         }
+        self.probes as f64 / items as f64
+    }
+
+    /// Resize the map if needed, it will initialize the map to the initial number of buckets of
+    /// the config, otherwise it will grow the capacity by the growth factor if table is not empty.
+    fn resize(&mut self) -> Result<(), Error> {
+        let target_size = self.config.grown(self.table.len())?;
+        self.resize_with_capacity(target_size)
+    }
 
+    /// Resize the map if needed, it will resize the map to desired capacity.
+    ///
+    /// Every entry is in the bucket of its hash, and the hash does not depend on the size of the
+    /// table, so the entries stay in place and only empty buckets are appended.
+    #[inline]
+    fn resize_with_capacity(&mut self, target_size: usize) -> Result<(), Error> {
+        let additional = target_size.saturating_sub(self.table.len());
+        self.table
+            .try_reserve_exact(additional)
+            .map_err(|_| Error::CapacityOverflow)?;
+        self.table.extend((0..additional).map(|_| Bucket::new()));
+        Ok(())
+    }
+
+    /// Returns a table with the given number of empty buckets, or Error::CapacityOverflow if it
+    /// cannot be allocated.
+    #[inline]
+    fn empty_table(n_buckets: usize) -> Result<Table<V, N>, Error> {
+        let mut table = Table::try_with_capacity(n_buckets)?;
+        table.extend((0..n_buckets).map(|_| Bucket::new()));
+        Ok(table)
+    }
+
+    /// Moves all the entries into the new table, hashing them with the current model. The
+    /// buckets are kept sorted along the axis of the current model.
+    #[inline]
+    fn refill_table(&mut self, mut new_table: Table<V, N>) {
+        let sort_axis = (self.hasher.axis() + 1) % D;
+        for e in self.table.iter_mut().flat_map(|bucket| bucket.drain(..)) {
+            let hash = make_hash_point(&self.hasher, &e.coords());
+            let index = new_table.bucket(hash);
+            Self::insert_into_bucket(&mut new_table[index], e, sort_axis);
+        }
+        self.probes = new_table.probes();
         self.table = new_table;
     }
 
-    /// Rehash the map.
+    /// Trains a new hasher on the points, and allocates an empty table that covers the hashes
+    /// of the points and of all the entries in the map. The points are sorted along the trained
+    /// axis.
+    fn train_hasher<P: PointLike<F, D>>(
+        &self,
+        ps: &mut [P],
+    ) -> Result<(LearnedHasher<M>, Table<V, N>), Error> {
+        // Loading data into trainer, and fit the data into a new model
+        let trainer = match self.config.axis {
+            Some(axis) => Trainer::with_points_along(ps, axis)?,
+            None => Trainer::with_points(ps)?,
+        };
+        let mut model = self.hasher.model.clone();
+        trainer.train(&mut model)?;
+
+        let axis = trainer.axis();
+        let max_key = ps
+            .iter()
+            .map(|p| p.coord(axis))
+            .fold(F::neg_infinity(), F::max);
+        self.hasher_with_table(model, axis, max_key, ps.len())
+    }
+
+    /// Returns a hasher with the trained model, and an empty table that covers the hashes of
+    /// the trained points and of all the entries in the map.
+    ///
+    /// # Arguments
+    /// * `model` - The trained model
+    /// * `axis` - The axis the model is trained along
+    /// * `max_key` - The largest key of the trained points along the axis
+    /// * `n_points` - The number of trained points
+    fn hasher_with_table(
+        &self,
+        model: M,
+        axis: usize,
+        max_key: F,
+        n_points: usize,
+    ) -> Result<(LearnedHasher<M>, Table<V, N>), Error> {
+        // Allocate table capacity before insert, covering the largest hash of all the entries
+        let max_key = self.iter().map(|e| e.coord(axis)).fold(max_key, F::max);
+        let max_hash: u64 = model.predict(max_key).floor().as_();
+        let n_buckets = usize::try_from(max_hash)
+            .ok()
+            .and_then(|hash| hash.checked_add(1))
+            .ok_or(Error::CapacityOverflow)?
+            .max(n_points);
+
+        let mut hasher = LearnedHasher::with_model(model);
+        hasher.set_axis(axis);
+        Ok((hasher, Self::empty_table(n_buckets)?))
+    }
+
+    /// Starts an incremental rehash, by retraining the model on the points of all the entries.
+    /// The entries are moved to the new table by the following inserts and removes, a few
+    /// buckets at a time. The map is left unchanged if the retraining fails.
+    ///
+    /// The table being migrated must be done, as the map holds a single old table.
+    fn start_rehash(&mut self) -> Result<(), Error> {
+        debug_assert!(self.migration.is_none());
+        let mut points: Vec<[F; D]> = self.iter().map(|e| e.coords()).collect();
+        let (hasher, table) = self.train_hasher(&mut points)?;
+
+        let old_hasher = mem::replace(&mut self.hasher, hasher);
+        let old_table = mem::replace(&mut self.table, table);
+        self.migration = Some(Migration::new(old_hasher, old_table));
+        self.probes = 0;
+        self.inserts = 0;
+        Ok(())
+    }
+
+    /// Retrains the model on the points of all the entries, and redistributes the entries into
+    /// a new table. Does nothing if the map is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model fails to fit, or the table cannot be allocated, in which
+    /// case the map is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+    /// for i in 0..10 {
+    ///     map.insert(Point::new(i as f64, 1.)).unwrap();
+    /// }
+    /// map.retrain().unwrap();
+    ///
+    /// assert_eq!(map.items(), 10);
+    /// assert!(map.get(&[5., 1.]).is_some());
+    /// ```
+    pub fn retrain(&mut self) -> Result<(), Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        self.finish_migration();
+        self.start_rehash()?;
+        self.finish_migration();
+        Ok(())
+    }
+
+    /// Starts an incremental rehash if the retrain policy asks for it after an insert into a
+    /// bucket of the given length. A failed retraining leaves the map unchanged, and the skew is
+    /// checked again after `min_inserts` inserts.
     #[inline]
-    fn rehash(&mut self) -> Result<(), Error> {
-        let mut old_data = Vec::with_capacity(self.items());
-        for p in self.table.iter_mut().flat_map(|bucket| bucket.drain(..)) {
-            old_data.push(p);
+    fn retrain_if_needed(&mut self, bucket_len: usize) {
+        if self.migration.is_some()
+            || !self
+                .config
+                .retrain_policy
+                .should_retrain(self.inserts, bucket_len, || self.mean_probe_len())
+        {
+            return;
+        }
+        match self.start_rehash() {
+            Ok(()) => self.migrate(MIGRATION_STEP),
+            Err(_) => self.inserts = 0,
         }
-        self.batch_insert(&mut old_data)
     }
 
-    /// Inner function for insert a single point into the map
+    /// Moves the entries of the next n buckets of the table being migrated into the table.
     #[inline]
-    fn insert_inner(&mut self, p: Point<F>) -> Option<Point<F>> {
-        // Resize if the table is empty or 3/4 size of the table is full
-        if self.table.is_empty() || self.items() > 3 * self.table.len() / 4 {
-            self.resize();
+    fn migrate(&mut self, n: usize) {
+        let Some(migration) = self.migration.as_mut() else {
+            return;
+        };
+        let entries: Vec<V> = migration.take_buckets(n).collect();
+        if migration.is_done() {
+            self.migration = None;
         }
-        let hash = make_hash_point::<M, F>(&mut self.hasher, &[p.x, p.y]);
-        self.insert_with_axis(p, hash)
+        for e in entries {
+            let hash = make_hash_point(&self.hasher, &e.coords());
+            self.insert_with_axis(e, hash);
+        }
+    }
+
+    /// Grows the table to cover the hash, by the growth factor of the config. The entries stay
+    /// in place, see `resize_with_capacity`.
+    #[inline]
+    fn grow_to_cover(&mut self, hash: u64) -> Result<(), Error> {
+        let target_size = usize::try_from(hash)
+            .map_err(|_| Error::CapacityOverflow)
+            .and_then(|hash| self.config.grown(hash))?;
+        self.resize_with_capacity(target_size)
+    }
+
+    /// Moves all the remaining entries of the table being migrated into the table.
+    #[inline]
+    fn finish_migration(&mut self) {
+        self.migrate(usize::MAX);
     }
 
-    /// Sequencial insert a point into the map.
+    /// Sequencial insert an entry into the map. Returns the replaced entry if an entry with
+    /// the same point already exists in the map.
+    ///
+    /// If the hash of the entry is out of the table, the table grows to cover it by the growth
+    /// factor, and the entry is placed with the current model instead of retraining it. The
+    /// model is retrained when the retrain policy of the map asks for it,
+    /// see `set_retrain_policy`, and the entries are moved to a new table incrementally: every
+    /// insert and remove moves a bounded number of buckets, and the queries look up both tables
+    /// until all the entries are moved.
+    ///
+    /// If the config of the map sets a dedup tolerance, the closest entry within the tolerance
+    /// of the entry is replaced, and returned, instead of an entry with the same point.
     ///
     /// # Arguments
-    /// * `p` - A Point<F> with float number
+    /// * `e` - An entry, either a Point<F> or a (Point<F>, V) pair
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if
+    ///
+    /// * a coordinate of the entry is NaN or infinite
+    /// * the coordinate along the periodic axis is outside of its domain
+    /// * the table cannot grow to the required capacity
     ///
     /// # Examples
     ///
@@ -315,68 +762,97 @@ where
     /// use lsph::{LearnedHashMap, LinearModel, Point};
     /// let a: Point<f64> = Point::new(0., 1.);
     /// let b: Point<f64> = Point::new(1., 0.);
-
+    ///
     /// let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
-    /// map.insert(a);
-    /// map.insert(b);
-
+    /// map.insert(a).unwrap();
+    /// map.insert(b).unwrap();
+    ///
     /// assert_eq!(map.items(), 2);
     /// assert_eq!(map.get(&[0., 1.]).unwrap(), &a);
     /// assert_eq!(map.get(&[1., 0.]).unwrap(), &b);
     /// ```
-    pub fn insert(&mut self, p: Point<F>) -> Option<Point<F>> {
-        // Resize if the table is empty or 3/4 size of the table is full
-        if self.table.is_empty() || self.items() > 3 * self.table.len() / 4 {
-            self.resize();
+    pub fn insert(&mut self, e: V) -> Result<Option<V>, Error> {
+        let p = e.coords();
+        if !p.iter().all(|c| c.is_finite()) {
+            return Err(Error::NonFiniteCoordinate);
+        }
+        if !self.in_domain(&p) {
+            return Err(Error::OutOfDomain);
+        }
+        let close = self.config.dedup_tolerance.and_then(|tolerance| {
+            let (close, _) = self.find_approx(&p, F::from_f64(tolerance)?)?;
+            Some(close.coords()).filter(|&close| close != p)
+        });
+        let Some(close) = close else {
+            return self.insert_exact(e);
+        };
+        // The entry is inserted first, so the map is left unchanged on error
+        self.insert_exact(e)?;
+        Ok(self.remove(&close))
+    }
+
+    /// Insert an entry into the map, replacing an entry with the same point. The coordinates of
+    /// the entry must be finite.
+    fn insert_exact(&mut self, e: V) -> Result<Option<V>, Error> {
+        let p = e.coords();
+        self.migrate(MIGRATION_STEP);
+        // Resize if the table is empty or filled beyond the load factor
+        if self.config.needs_growth(self.items(), self.table.len()) {
+            self.resize()?;
         }
 
-        let hash = make_hash_point::<M, F>(&mut self.hasher, &[p.x, p.y]);
+        let hash = make_hash_point(&self.hasher, &p);
         // resize if hash index is larger or equal to the table capacity
-        if hash >= self.table.capacity() as u64 {
-            self.resize_with_capacity(hash as usize * 2);
-            self.insert_with_axis(p, hash);
-            match self.rehash() {
-                Ok(_) => None,
-                Err(_err) => {
-                    None
-                }
-            }
-        } else {
-            self.insert_with_axis(p, hash)
+        if hash >= self.table.len() as u64 {
+            self.grow_to_cover(hash)?;
         }
+        // An entry with the same point may not be migrated yet
+        let migrated = self.migration.as_mut().and_then(|m| m.remove(&p));
+        let replaced = self.insert_with_axis(e, hash).or(migrated);
+        if replaced.is_none() {
+            self.items += 1;
+        }
+        self.inserts += 1;
+        let bucket_len = self.table[self.table.bucket(hash)].len();
+        self.retrain_if_needed(bucket_len);
+        Ok(replaced)
     }
 
-    /// Insert a point into the map along the given axis.
+    /// Insert an entry into the map along the given axis. Entries in a bucket are sorted along
+    /// the axis next to the hashed axis. Returns the replaced entry, the number of items is
+    /// left to the caller.
     ///
     /// # Arguments
-    /// * `p_value` - A float number represent the key of a 2d point
+    /// * `e` - An entry to insert
+    /// * `hash` - A hash value of the entry's point
     #[inline]
-    fn insert_with_axis(&mut self, p: Point<F>, hash: u64) -> Option<Point<F>> {
-        let mut insert_index = 0;
+    fn insert_with_axis(&mut self, e: V, hash: u64) -> Option<V> {
         let bucket_index = self.table.bucket(hash);
         let bucket = &mut self.table[bucket_index];
-        if self.hasher.sort_by_x() {
-            // Get index from the hasher
-            for ep in bucket.iter_mut() {
-                if ep == &mut p.clone() {
-                    return Some(mem::replace(ep, p));
-                }
-                if ep.y < p.y() {
-                    insert_index += 1;
-                }
+        let len = bucket.len();
+        let replaced = Self::insert_into_bucket(bucket, e, (self.hasher.axis() + 1) % D);
+        if replaced.is_none() {
+            self.probes += 2 * len + 1;
+        }
+        replaced
+    }
+
+    /// Insert an entry into the bucket, sorted along the given axis. Returns the replaced
+    /// entry.
+    #[inline]
+    fn insert_into_bucket(bucket: &mut Bucket<V, N>, e: V, sort_axis: usize) -> Option<V> {
+        let mut insert_index = 0;
+        let p = e.coords();
+        for ee in bucket.iter_mut() {
+            let ep = ee.coords();
+            if ep == p {
+                return Some(mem::replace(ee, e));
             }
-        } else {
-            for ep in bucket.iter_mut() {
-                if ep == &mut p.clone() {
-                    return Some(mem::replace(ep, p));
-                }
-                if ep.x < p.x() {
-                    insert_index += 1;
-                }
+            if ep[sort_axis] < p[sort_axis] {
+                insert_index += 1;
             }
         }
-        bucket.insert(insert_index, p);
-        self.items += 1;
+        bucket.insert(insert_index, e);
         None
     }
 
@@ -402,52 +878,61 @@ where
         self.hasher.model.fit_tuple(data)
     }
 
-    /// Inner function for batch insert
+    /// Inner function for batch insert, the table must cover the hashes of all the entries
     #[inline]
-    fn batch_insert_inner(&mut self, ps: &[Point<F>]) {
-        // Allocate table capacity before insert
-        let n = ps.len();
-        self.resize_with_capacity(n);
-        for p in ps.iter() {
-            self.insert_inner(*p);
+    fn batch_insert_inner(&mut self, ps: &[V]) {
+        for e in ps.iter() {
+            let hash = make_hash_point(&self.hasher, &e.coords());
+            if self.insert_with_axis(e.clone(), hash).is_none() {
+                self.items += 1;
+            }
         }
     }
 
-    /// Batch insert a batch of 2d data into the map.
+    /// Batch insert a batch of 2d data into the map. The model is retrained with the batch, and
+    /// the entries are sorted along the trained axis afterwards.
     ///
     /// # Arguments
-    /// * `ps` - A list of point number
+    /// * `ps` - A list of entries
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if
+    ///
+    /// * a coordinate of an entry is NaN or infinite
+    /// * the coordinate of an entry along the periodic axis is outside of its domain
+    /// * all the entries share the same point, so no axis can be learned
+    /// * the model fails to fit, or is not monotonic
+    /// * the table cannot be allocated
+    ///
+    /// The map is left unchanged on error.
     ///
     /// # Examples
     ///
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     ///
     /// assert_eq!(map.get(&[1., 1.]).is_some(), true);
     /// ```
     #[inline]
-    pub fn batch_insert(&mut self, ps: &mut [Point<F>]) -> Result<(), Error> {
-        // Select suitable axis for training
-        use crate::geometry::Axis;
-        use crate::models::Trainer;
+    pub fn batch_insert(&mut self, ps: &mut [V]) -> Result<(), Error> {
+        if ps.is_empty() {
+            return Ok(());
+        }
+        if !ps.iter().all(|e| self.in_domain(&e.coords())) {
+            return Err(Error::OutOfDomain);
+        }
+        self.finish_migration();
 
-        // Loading data into trainer
-        if let Ok(trainer) = Trainer::with_points(ps) {
-            trainer.train(&mut self.hasher.model).unwrap();
-            let axis = trainer.axis();
-            match axis {
-                Axis::X => self.hasher.set_sort_by_x(true),
-                _ => self.hasher.set_sort_by_x(false),
-            };
+        let (hasher, new_table) = self.train_hasher(ps)?;
+        self.hasher = hasher;
+        self.refill_table(new_table);
+        self.inserts = 0;
 
-            // Fit the data into model
-            self.model_fit(trainer.train_x(), trainer.train_y())
-                .unwrap();
-            // Batch insert into the map
-            self.batch_insert_inner(ps);
-        }
+        // Batch insert into the map
+        self.batch_insert_inner(ps);
         Ok(())
     }
 
@@ -466,124 +951,177 @@ where
     ///              left       right
     ///              hash       hash
     /// ```
+    /// With a periodic axis, see `MapConfig::periodic`, a range whose bottom left is larger than
+    /// its top right along the axis wraps around the boundary of the domain.
+    ///
     /// # Arguments
     ///
-    /// * `bottom_left` - A tuple containing a pair of points that represent the bottom left of the
-    ///   range.
+    /// * `bottom_left` - A point-like data that represents the bottom left of the range.
     ///
-    /// * `top_right` - A tuple containing a pair of points that represent the top right of the
-    ///   range.
+    /// * `top_right` - A point-like data that represents the top right of the range.
     #[inline]
-    pub fn range_search(
-        &mut self,
-        bottom_left: &[F; 2],
-        top_right: &[F; 2],
-    ) -> Option<Vec<Point<F>>> {
-        let mut right_hash = make_hash_point(&mut self.hasher, top_right) as usize;
-        if right_hash > self.table.capacity() {
-            right_hash = self.table.capacity() - 1;
-        }
-        let left_hash = make_hash_point(&mut self.hasher, bottom_left) as usize;
-        if left_hash > self.table.capacity() || left_hash > right_hash {
-            return None;
-        }
-        let mut result: Vec<Point<F>> = Vec::new();
-        for i in left_hash..=right_hash {
-            let bucket = &self.table[i];
-            for item in bucket.iter() {
-                if item.x >= bottom_left[0]
-                    && item.x <= top_right[0]
-                    && item.y >= bottom_left[1]
-                    && item.y <= top_right[1]
-                {
-                    result.push(*item);
-                }
-            }
-        }
+    pub fn range_search<P: PointLike<F, D>>(
+        &self,
+        bottom_left: &P,
+        top_right: &P,
+    ) -> Option<Vec<V>> {
+        let result: Vec<V> = self
+            .query()
+            .range(&bottom_left.coords(), &top_right.coords())
+            .into_iter()
+            .cloned()
+            .collect();
         if result.is_empty() {
             return None;
         }
         Some(result)
     }
 
-    /// Returns Option<Vec<Point<F>>> if points are found in the map with given range
+    /// Returns Option<Vec<V>> if points are found in the map within the bounding square of the
+    /// given radius. The result may contain points in the corners of the square, which are
+    /// farther than `radius` away from the query point.
     ///
     /// # Arguments
     /// * `query_point` - A Point data for querying
-    /// * `radius` - A radius value
+    /// * `radius` - A radius value, which is half of the side of the square
     ///
     /// # Examples
     ///
     /// ```
-    /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
+    /// use lsph::{LearnedHashMap, LinearModel};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
-    /// assert_eq!(map.range_search(&[0., 0.], &[3., 3.]).is_some(), true);
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// assert_eq!(map.square_range(&[2., 1.], 1.).unwrap().len(), 3);
     /// ```
     #[inline]
-    pub fn radius_range(&mut self, query_point: &[F; 2], radius: F) -> Option<Vec<Point<F>>> {
+    pub fn square_range<P: PointLike<F, D>>(&self, query_point: &P, radius: F) -> Option<Vec<V>> {
+        let query_point = query_point.coords();
         self.range_search(
-            &[query_point[0] - radius, query_point[1] - radius],
-            &[query_point[0] + radius, query_point[1] + radius],
+            &array::from_fn(|i| query_point[i] - radius),
+            &array::from_fn(|i| query_point[i] + radius),
         )
     }
 
-    /// Find the local minimum distance between query points and cadidates neighbors, then store
-    /// the cadidates neighbors in the min_heap.
+    /// Returns Option<Vec<V>> if points are found in the map within the given radius of the
+    /// query point, measured by the metric of the map, see `MapConfig::metric`. The radius is
+    /// in metres in the geographic mode.
+    ///
+    /// # Arguments
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    ///
+    /// # Examples
     ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// assert_eq!(map.range_search(&[0., 0.], &[3., 3.]).is_some(), true);
+    /// assert_eq!(map.radius_range(&[2., 1.], 1.).unwrap().len(), 2);
+    /// ```
+    #[inline]
+    pub fn radius_range<P: PointLike<F, D>>(&self, query_point: &P, radius: F) -> Option<Vec<V>> {
+        let result: Vec<V> = self
+            .radius_range_with_distance(query_point, radius)?
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect();
+        Some(result)
+    }
+
+    /// Returns Option<Vec<(V, F)>> if points are found in the map within the given radius of the
+    /// query point, each hit is paired with its distance to the query point measured by the
+    /// metric of the map, see `MapConfig::metric`.
     ///
     /// # Arguments
-    /// * `heap` - mutable borrow of an BinaryHeap
-    /// * `local_hash` - A hash index of local bucket
-    /// * `query_point` - A Point data
-    /// * `min_d` - minimum distance
-    /// * `nearest_neighbor` - mutable borrow of an point data, which is the nearest neighbor at
-    ///   search index bucket
-    #[inline]
-    fn local_min_heap(
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let hits = map.radius_range_with_distance(&[2., 1.], 1.).unwrap();
+    /// assert!(hits.contains(&(Point::new(1., 1.), 1.)));
+    /// ```
+    ///
+    /// In the geographic mode, the radius and the distances are in metres:
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Metric, Point};
+    /// let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+    ///     .metric(Metric::Haversine)
+    ///     .build()
+    ///     .unwrap();
+    /// let mut cities = vec![
+    ///     Point::new(-37.8136, 144.9631), // Melbourne
+    ///     Point::new(-38.1499, 144.3617), // Geelong
+    ///     Point::new(-33.8688, 151.2093), // Sydney
+    /// ];
+    /// map.batch_insert(&mut cities).unwrap();
+    ///
+    /// let hits = map.radius_range_with_distance(&[-37.8136, 144.9631], 100_000.).unwrap();
+    /// assert_eq!(hits.len(), 2);
+    /// assert!(hits.iter().all(|&(_, metres)| metres < 65_000.));
+    /// ```
+    #[inline]
+    pub fn radius_range_with_distance<P: PointLike<F, D>>(
         &self,
-        heap: &mut BinaryHeap<NearestNeighborState<F>>,
-        local_hash: u64,
-        query_point: &[F; 2],
-        min_d: &mut F,
-        nearest_neighbor: &mut Point<F>,
-    ) {
-        let bucket = &self.table[local_hash as usize];
-        if !bucket.is_empty() {
-            for p in bucket.iter() {
-                let d = Euclidean::distance(query_point, &[p.x, p.y]);
-                heap.push(NearestNeighborState {
-                    distance: d,
-                    point: *p,
-                });
-            }
-        }
-        if let Some(v) = heap.pop() {
-            let local_min_d = v.distance;
-            // Update the nearest neighbour and minimum distance
-            if &local_min_d < min_d {
-                *nearest_neighbor = v.point;
-                *min_d = local_min_d;
-            }
+        query_point: &P,
+        radius: F,
+    ) -> Option<Vec<(V, F)>> {
+        let result: Vec<(V, F)> = self
+            .query()
+            .radius(&query_point.coords(), radius)
+            .into_iter()
+            .map(|(e, d)| (e.clone(), d))
+            .collect();
+        if result.is_empty() {
+            return None;
         }
+        Some(result)
     }
 
-    /// Calculates the horizontal distance between query_point and bucket at index with given hash.
+    /// Returns Option<Vec<(V, F)>> if points are found in the map within the given radius of the
+    /// query point measured by the distance Dist, each hit is paired with its distance to the
+    /// query point. The radius is in the unit of Dist, e.g. the square of the Euclidean radius
+    /// for `SquaredEuclidean`.
     ///
     /// # Arguments
-    /// * `hash` - A hash index of the bucket
-    /// * `query_point` - A Point data
-    #[inline]
-    fn horizontal_distance(&mut self, query_point: &[F; 2], hash: u64) -> F {
-        let x = unhash(&mut self.hasher, hash);
-        match self.hasher.sort_by_x() {
-            true => Euclidean::distance(&[query_point[0], F::zero()], &[x, F::zero()]),
-            false => Euclidean::distance(&[query_point[1], F::zero()], &[x, F::zero()]),
+    /// * `query_point` - A Point data for querying
+    /// * `radius` - A radius value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{geometry::distance::Manhattan, LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let hits = map.radius_range_with::<Manhattan<f64>, _>(&[2., 1.], 2.).unwrap();
+    /// assert_eq!(hits.len(), 3);
+    /// assert!(hits.contains(&(Point::new(3., 2.), 2.)));
+    /// ```
+    pub fn radius_range_with<Dist, P>(&self, query_point: &P, radius: F) -> Option<Vec<(V, F)>>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        let result: Vec<(V, F)> = self
+            .query()
+            .radius_with::<Dist>(&query_point.coords(), radius)
+            .into_iter()
+            .map(|(e, d)| (e.clone(), d))
+            .collect();
+        if result.is_empty() {
+            return None;
         }
+        Some(result)
     }
 
     /// Nearest neighbor search for the closest point for given query point
-    /// Returns the closest point
+    /// Returns the closest point, measured by the metric of the map, see `MapConfig::metric`
     ///```text
     ///      |
     ///      |            .
@@ -599,116 +1137,373 @@ where
     ///```
     /// # Arguments
     ///
-    /// * `query_point` - A tuple containing a pair of points for querying
+    /// * `query_point` - A point-like data for querying
     ///
     /// # Examples
     ///
     /// ```
     /// use lsph::{LearnedHashMap, LinearModel, LearnedHasher};
     /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-    /// let (mut map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
     /// assert_eq!(map.nearest_neighbor(&[2., 1.]).is_some(), true);
     /// ```
     #[inline]
-    pub fn nearest_neighbor(&mut self, query_point: &[F; 2]) -> Option<Point<F>> {
-        let mut hash = make_hash_point(&mut self.hasher, query_point);
-        let max_capacity = self.table.capacity() as u64;
-
-        // if hash out of max bound, still search right most bucket
-        if hash > max_capacity {
-            hash = max_capacity - 1;
-        }
-
-        let mut heap = BinaryHeap::new();
-        let mut min_d = F::max_value();
-        let mut nearest_neighbor = Point::default();
-
-        // Searching at current hash index
-        self.local_min_heap(
-            &mut heap,
-            hash,
-            query_point,
-            &mut min_d,
-            &mut nearest_neighbor,
-        );
-
-        // Measure left horizontal distance from current bucket to left hash bucket
-        // left hash must >= 0
-        let mut left_hash = hash.saturating_sub(1);
-        // Unhash the left_hash, then calculate the vertical distance between
-        // left hash point and query point
-        let mut left_hash_d = self.horizontal_distance(query_point, left_hash);
-
-        // Iterate over left
-        while left_hash_d < min_d {
-            self.local_min_heap(
-                &mut heap,
-                left_hash,
-                query_point,
-                &mut min_d,
-                &mut nearest_neighbor,
-            );
+    pub fn nearest_neighbor<P: PointLike<F, D>>(&self, query_point: &P) -> Option<V> {
+        self.k_nearest_neighbors(query_point, 1).pop()
+    }
 
-            // break before update
-            if left_hash == 0 {
-                break;
-            }
+    /// Nearest neighbor search for the closest point for given query point, measured by the
+    /// distance Dist instead of the Euclidean distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_point` - A point-like data for querying
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{geometry::distance::Chebyshev, LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [1.2, 0.], [3., 3.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let neighbor = map.nearest_neighbor_with::<Chebyshev<f64>, _>(&[0., 0.]);
+    /// assert_eq!(neighbor, Some(Point::new(1., 1.)));
+    /// ```
+    #[inline]
+    pub fn nearest_neighbor_with<Dist, P>(&self, query_point: &P) -> Option<V>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        self.k_nearest_neighbors_with::<Dist, P>(query_point, 1)
+            .pop()
+    }
 
-            // Update next right side bucket distance
-            left_hash = left_hash.saturating_sub(1);
-            left_hash_d = self.horizontal_distance(query_point, left_hash);
-        }
-
-        // Measure right vertical distance from current bucket to right hash bucket
-        let mut right_hash = hash + 1;
-        // Unhash the right_hash, then calculate the vertical distance between
-        // right hash point and query point
-        let mut right_hash_d = self.horizontal_distance(query_point, right_hash);
-
-        // Iterate over right
-        while right_hash_d < min_d {
-            self.local_min_heap(
-                &mut heap,
-                right_hash,
-                query_point,
-                &mut min_d,
-                &mut nearest_neighbor,
-            );
+    /// K nearest neighbors search for the k closest points for given query point, measured by the
+    /// metric of the map, see `MapConfig::metric`.
+    /// Returns the k closest points ordered by distance, or less than k points if the map holds
+    /// less than k points.
+    ///
+    /// The search starts at the bucket of the query point, then expands to the left and right
+    /// buckets. The expansion on each side stops as soon as the k-th nearest distance is not
+    /// larger than the lower bound of the distance between the query point and the boundary of
+    /// the next bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let neighbors = map.k_nearest_neighbors(&[2., 1.], 2);
+    /// assert_eq!(neighbors, vec![Point::new(2., 1.), Point::new(1., 1.)]);
+    /// ```
+    #[inline]
+    pub fn k_nearest_neighbors<P: PointLike<F, D>>(&self, query_point: &P, k: usize) -> Vec<V> {
+        self.query()
+            .nearest(&query_point.coords(), k, |_| true)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
 
-            // Move to next right bucket
-            right_hash += 1;
+    /// K nearest neighbors search for the k closest points for given query point, measured by
+    /// the distance Dist instead of the Euclidean distance. The buckets are pruned by the lower
+    /// bound of Dist, see `Distance::lower_bound`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{geometry::distance::Manhattan, LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[0., 0.], [1.5, 1.5], [0., 2.5]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let neighbors = map.k_nearest_neighbors_with::<Manhattan<f64>, _>(&[0., 0.], 2);
+    /// assert_eq!(neighbors, vec![Point::new(0., 0.), Point::new(0., 2.5)]);
+    /// ```
+    #[inline]
+    pub fn k_nearest_neighbors_with<Dist, P>(&self, query_point: &P, k: usize) -> Vec<V>
+    where
+        Dist: Distance<F = F>,
+        P: PointLike<F, D>,
+    {
+        self.query()
+            .nearest_with::<Dist, _>(&query_point.coords(), k, |_| true)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
 
-            // break after update
-            if right_hash == self.table.capacity() as u64 {
-                break;
-            }
-            // Update next right side bucket distance
-            right_hash_d = self.horizontal_distance(query_point, right_hash);
+    /// Nearest neighbor search for the closest point matching the predicate for given query
+    /// point, measured by the metric of the map, see `MapConfig::metric`.
+    ///
+    /// The search expands to the left and right buckets until a matching point is found, and
+    /// the lower bound of the distance to the next bucket exceeds the distance to that point.
+    /// The points not matching the predicate do not stop the expansion.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_point` - A point-like data for querying
+    /// * `predicate` - Returns true for the points to consider
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point, PointLike};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let neighbor = map.nearest_neighbor_where(&[2., 1.], |p| p.y() > 1.);
+    /// assert_eq!(neighbor, Some(Point::new(3., 2.)));
+    /// ```
+    #[inline]
+    pub fn nearest_neighbor_where<P, G>(&self, query_point: &P, predicate: G) -> Option<V>
+    where
+        P: PointLike<F, D>,
+        G: FnMut(&V) -> bool,
+    {
+        self.k_nearest_neighbors_where(query_point, 1, predicate)
+            .pop()
+    }
+
+    /// K nearest neighbors search for the k closest points matching the predicate for given
+    /// query point, measured by the metric of the map, see `nearest_neighbor_where`.
+    /// Returns the k closest matching points ordered by distance, or less than k points if the
+    /// map holds less than k matching points.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_point` - A point-like data for querying
+    /// * `k` - number of neighbors to search
+    /// * `predicate` - Returns true for the points to consider
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point, PointLike};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    /// let neighbors = map.k_nearest_neighbors_where(&[1., 1.], 2, |p| p.x() > 2.);
+    /// assert_eq!(neighbors, vec![Point::new(3., 2.), Point::new(4., 4.)]);
+    /// ```
+    #[inline]
+    pub fn k_nearest_neighbors_where<P, G>(
+        &self,
+        query_point: &P,
+        k: usize,
+        mut predicate: G,
+    ) -> Vec<V>
+    where
+        P: PointLike<F, D>,
+        G: FnMut(&V) -> bool,
+    {
+        self.query()
+            .nearest(&query_point.coords(), k, |e| predicate(e))
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+}
+
+impl<M, F, V, const N: usize> LearnedHashMap<M, F, V, 2, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, 2> + Clone,
+{
+    /// Polygon search finds all points inside the given polygon, or on its boundary.
+    /// Returns all the points inside the polygon.
+    ///
+    /// Only the buckets between the hashes of the bounding box of the polygon are scanned, and
+    /// the points within the bounding box are tested against the polygon.
+    ///
+    /// # Arguments
+    ///
+    /// * `exterior` - The vertices of the polygon in either orientation, the last vertex may
+    ///   repeat the first one. On a map with a periodic axis, the polygon may cross the boundary
+    ///   of the domain or lie outside of it, and the points are tested at their images shifted
+    ///   by whole periods into the extent of the polygon.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// let triangle = [[0., 0.], [4., 0.], [4., 4.]];
+    /// let mut found = map.polygon_search(&triangle).unwrap();
+    /// found.sort_by(|a, b| a.x().partial_cmp(&b.x()).unwrap());
+    /// assert_eq!(found, vec![Point::new(1., 1.), Point::new(2., 1.), Point::new(3., 2.), Point::new(4., 4.)]);
+    /// ```
+    #[inline]
+    pub fn polygon_search(&self, exterior: &[[F; 2]]) -> Option<Vec<V>> {
+        self.polygon_search_with_holes::<[[F; 2]; 0]>(exterior, &[])
+    }
+
+    /// Polygon search finds all points inside the given polygon with holes, or on its boundary.
+    /// Returns all the points inside the polygon and outside of its holes, see
+    /// `polygon_search`.
+    ///
+    /// # Arguments
+    ///
+    /// * `exterior` - The vertices of the exterior of the polygon
+    /// * `holes` - The vertices of every hole of the polygon
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let point_data = vec![[1., 1.], [2., 2.], [3., 3.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// let square = [[0., 0.], [4., 0.], [4., 4.], [0., 4.]];
+    /// let hole = vec![[1.5, 1.5], [2.5, 1.5], [2.5, 2.5], [1.5, 2.5]];
+    /// let found = map.polygon_search_with_holes(&square, &[hole]).unwrap();
+    /// assert_eq!(found.len(), 2);
+    /// assert!(!found.contains(&Point::new(2., 2.)));
+    /// ```
+    pub fn polygon_search_with_holes<H>(&self, exterior: &[[F; 2]], holes: &[H]) -> Option<Vec<V>>
+    where
+        H: AsRef<[[F; 2]]>,
+    {
+        let result: Vec<V> = self
+            .query()
+            .polygon(exterior, holes)
+            .into_iter()
+            .cloned()
+            .collect();
+        if result.is_empty() {
+            return None;
         }
+        Some(result)
+    }
+}
 
-        Some(nearest_neighbor)
+impl<M, F, const D: usize, const N: usize> LearnedHashMap<M, F, Point<F, D>, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+{
+    /// Returns a default LearnedHashMap with Model and Float type
+    ///
+    /// # Arguments
+    /// * `data` - A slice of points for the map, e.g. `[F; D]` arrays
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel};
+    /// let data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let map = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data);
+    /// ```
+    #[inline]
+    pub fn with_data<P: PointLike<F, D>>(data: &[P]) -> Result<(Self, Vec<Point<F, D>>), Error> {
+        use crate::helper::convert_to_points;
+        let mut map = LearnedHashMap::with_capacity(data.len());
+        let mut ps = convert_to_points(data).ok_or(Error::EmptyVal)?;
+        map.batch_insert(&mut ps)?;
+        Ok((map, ps))
+    }
+}
+
+impl<M, F, T, const D: usize, const N: usize> LearnedHashMap<M, F, (Point<F, D>, T), D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    T: Clone,
+{
+    /// Returns Option<&mut T> of the payload stored with given point data, the payload can be
+    /// updated in place.
+    ///
+    /// # Arguments
+    /// * `p` - A point-like data for querying, e.g. an array of coordinates
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, u32)>::new();
+    /// map.insert((Point::new(1., 1.), 1)).unwrap();
+    ///
+    /// *map.get_mut(&[1., 1.]).unwrap() += 1;
+    /// assert_eq!(map.get(&[1., 1.]).unwrap().1, 2);
+    /// ```
+    #[inline]
+    pub fn get_mut<P: PointLike<F, D>>(&mut self, p: &P) -> Option<&mut T> {
+        let p = p.coords();
+        let hash = make_hash_point(&self.hasher, &p) as usize;
+        let e = match self.table.get_mut(hash) {
+            Some(bucket) => bucket.iter_mut().find(|(ep, _)| ep.coords == p),
+            None => None,
+        };
+        e.or_else(|| self.migration.as_mut()?.get_mut(&p))
+            .map(|(_, v)| v)
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> Buckets<F, D> for LearnedHashMap<M, F, V, D, N>
+where
+    F: Float,
+    M: Model<F = F> + Default,
+    V: PointLike<F, D>,
+{
+    type Model = M;
+    type Entry<'a>
+        = &'a V
+    where
+        Self: 'a;
+
+    #[inline]
+    fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    #[inline]
+    fn n_buckets(&self) -> usize {
+        self.table.len()
+    }
+
+    #[inline]
+    fn bucket(&self, index: usize) -> impl Iterator<Item = &V> {
+        self.table[index].iter()
     }
 }
 
-pub struct Iter<'a, M, F>
+/// An iterator over the entries of a LearnedHashMap, in bucket order.
+pub struct Iter<'a, M, F, V = Point<F>, const D: usize = 2, const N: usize = 6>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    map: &'a LearnedHashMap<M, F>,
+    map: &'a LearnedHashMap<M, F, V, D, N>,
+    migrating: bool,
     bucket: usize,
     at: usize,
 }
 
-impl<'a, M, F> Iterator for Iter<'a, M, F>
+impl<'a, M, F, V, const D: usize, const N: usize> Iterator for Iter<'a, M, F, V, D, N>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    type Item = &'a Point<F>;
+    type Item = &'a V;
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match self.map.table.get(self.bucket) {
+            // The entries of the table being migrated follow the entries of the table
+            let table = match (self.migrating, &self.map.migration) {
+                (false, _) => &self.map.table,
+                (true, Some(migration)) => &migration.table,
+                (true, None) => break None,
+            };
+            match table.get(self.bucket) {
                 Some(bucket) => {
                     match bucket.get(self.at) {
                         Some(p) => {
@@ -723,43 +1518,50 @@ where
                         }
                     }
                 }
+                None if !self.migrating => {
+                    self.migrating = true;
+                    self.bucket = 0;
+                    self.at = 0;
+                }
                 None => break None,
             }
         }
     }
 }
 
-impl<'a, M, F> IntoIterator for &'a LearnedHashMap<M, F>
+impl<'a, M, F, V, const D: usize, const N: usize> IntoIterator for &'a LearnedHashMap<M, F, V, D, N>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    type Item = &'a Point<F>;
-    type IntoIter = Iter<'a, M, F>;
+    type Item = &'a V;
+    type IntoIter = Iter<'a, M, F, V, D, N>;
     fn into_iter(self) -> Self::IntoIter {
         Iter {
             map: self,
+            migrating: false,
             bucket: 0,
             at: 0,
         }
     }
 }
 
-pub struct IntoIter<M, F>
+/// An owning iterator over the entries of a LearnedHashMap.
+pub struct IntoIter<M, F, V = Point<F>, const D: usize = 2, const N: usize = 6>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    map: LearnedHashMap<M, F>,
+    map: LearnedHashMap<M, F, V, D, N>,
     bucket: usize,
 }
 
-impl<M, F> Iterator for IntoIter<M, F>
+impl<M, F, V, const D: usize, const N: usize> Iterator for IntoIter<M, F, V, D, N>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    type Item = Point<F>;
+    type Item = V;
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             match self.map.table.get_mut(self.bucket) {
@@ -770,19 +1572,26 @@ where
                         continue;
                     }
                 },
-                None => break None,
+                None => match self.map.migration.take() {
+                    // Continue with the entries of the table being migrated
+                    Some(migration) => {
+                        self.map.table = migration.table;
+                        self.bucket = 0;
+                    }
+                    None => break None,
+                },
             }
         }
     }
 }
 
-impl<M, F> IntoIterator for LearnedHashMap<M, F>
+impl<M, F, V, const D: usize, const N: usize> IntoIterator for LearnedHashMap<M, F, V, D, N>
 where
     F: Float,
     M: Model<F = F> + Default + Clone,
 {
-    type Item = Point<F>;
-    type IntoIter = IntoIter<M, F>;
+    type Item = V;
+    type IntoIter = IntoIter<M, F, V, D, N>;
     fn into_iter(self) -> Self::IntoIter {
         IntoIter {
             map: self,
@@ -794,7 +1603,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::geometry::Point;
+    use crate::geometry::{polygon_contains, Point};
     use crate::models::LinearModel;
     use crate::test_utilities::*;
 
@@ -804,8 +1613,8 @@ mod tests {
         let b: Point<f64> = Point::new(1., 0.);
 
         let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
-        map.insert(a);
-        map.insert(b);
+        map.insert(a).unwrap();
+        map.insert(b).unwrap();
 
         assert_eq!(map.items(), 2);
         assert_eq!(map.get(&[0., 1.]).unwrap(), &a);
@@ -817,11 +1626,11 @@ mod tests {
         let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
         let a: Point<f64> = Point::new(0., 1.);
         let b: Point<f64> = Point::new(1., 0.);
-        let res = map.insert(a);
+        let res = map.insert(a).unwrap();
         assert_eq!(map.items(), 1);
         assert_eq!(res, None);
 
-        let res = map.insert(b);
+        let res = map.insert(b).unwrap();
         assert_eq!(map.items(), 2);
         assert_eq!(res, None);
     }
@@ -829,7 +1638,7 @@ mod tests {
     #[test]
     fn with_data() {
         let data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
-        let (mut map, _points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data).unwrap();
+        let (map, _points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data).unwrap();
         assert!(map.get(&[1., 1.]).is_some());
     }
 
@@ -870,15 +1679,75 @@ mod tests {
         map.batch_insert(&mut data).unwrap();
 
         let a: Point<f64> = Point::new(10., 10.);
-        map.insert(a);
+        map.insert(a).unwrap();
         assert_eq!(Some(&a), map.get(&[10., 10.]));
 
         let b: Point<f64> = Point::new(100., 100.);
-        map.insert(b);
+        map.insert(b).unwrap();
         assert_eq!(Some(&b), map.get(&[100., 100.]));
         assert_eq!(None, map.get(&[100., 101.]));
     }
 
+    #[test]
+    fn insert_non_finite() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            map.insert(Point::new(f64::NAN, 1.))
+        );
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            map.insert(Point::new(1., f64::INFINITY))
+        );
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn batch_insert_errors_leave_map_unchanged() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut [Point::new(1., 1.), Point::new(2., 3.)])
+            .unwrap();
+
+        let mut nan = vec![Point::new(5., 5.), Point::new(f64::NAN, 1.)];
+        assert_eq!(Err(Error::NonFiniteCoordinate), map.batch_insert(&mut nan));
+        let mut same = vec![Point::new(5., 5.), Point::new(5., 5.)];
+        assert_eq!(Err(Error::DegenerateAxis), map.batch_insert(&mut same));
+
+        assert_eq!(map.items(), 2);
+        assert!(map.get(&[1., 1.]).is_some());
+        assert!(map.get(&[2., 3.]).is_some());
+        assert_eq!(Ok(()), map.batch_insert(&mut []));
+    }
+
+    #[test]
+    fn remove_absent() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        assert_eq!(None, map.remove(&Point::new(1., 1.)));
+
+        let data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+        let (mut map, _points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data).unwrap();
+        assert_eq!(None, map.remove(&Point::new(2., 2.)));
+        assert_eq!(None, map.remove(&Point::new(100., 100.)));
+        assert_eq!(map.items(), 4);
+        assert!(map.remove(&Point::new(2., 1.)).is_some());
+        assert!(map.remove(&Point::new(2., 1.)).is_none());
+        assert_eq!(map.items(), 3);
+    }
+
+    #[test]
+    fn get_out_of_table() {
+        let data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+        let (map, _points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data).unwrap();
+        assert_eq!(None, map.get(&[1000., 1.]));
+        assert_eq!(None, map.find_by_hash(1000, &[1., 1.]));
+    }
+
+    #[test]
+    fn error_display() {
+        let err: Box<dyn std::error::Error> = Box::new(Error::NotMonotonic);
+        assert_eq!("model is not monotonic", err.to_string());
+    }
+
     #[test]
     fn range_search() {
         let mut data: Vec<Point<f64>> = vec![
@@ -891,7 +1760,6 @@ mod tests {
         let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
         map.batch_insert(&mut data).unwrap();
 
-
         let found: Vec<Point<f64>> =
             vec![Point::new(1., 1.), Point::new(2., 2.), Point::new(3., 3.)];
 
@@ -903,6 +1771,35 @@ mod tests {
         assert_eq!(None, map.range_search(&[4., 2.], &[5., 3.]));
     }
 
+    #[test]
+    fn range_search_after_refill() {
+        // Pairs of entries share a bucket along x, sorted along y against the order of their x
+        let mut data: Vec<Point<f64>> = (0..10)
+            .flat_map(|i| {
+                let x = i as f64 * 10.;
+                [Point::new(x, 3.), Point::new(x + 1e-6, 0.)]
+            })
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut data).unwrap();
+        assert_eq!(0, map.hasher.axis());
+
+        // The batch retrains the model along y, and the entries above move into the first bucket
+        let mut batch: Vec<Point<f64>> = (0..10)
+            .map(|i| Point::new(i as f64, 100. + i as f64 * 10.))
+            .collect();
+        map.batch_insert(&mut batch).unwrap();
+        assert_eq!(1, map.hasher.axis());
+        for bucket in map.table.iter() {
+            assert!(bucket.windows(2).all(|w| w[0].x() <= w[1].x()));
+        }
+
+        let mut expected = data.clone();
+        expected.sort_by(|a, b| a.x().partial_cmp(&b.x()).unwrap());
+        assert!(map.table[0].len() > 1);
+        assert_eq!(Some(expected), map.range_search(&[0., 0.], &[100., 3.]));
+    }
+
     #[test]
     fn test_nearest_neighbor() {
         let points = create_random_point_type_points(1000, SEED_1);
@@ -921,9 +1818,1039 @@ mod tests {
                 }
             }
             let map_nearest = map
-                .nearest_neighbor(&[sample_point.x, sample_point.y])
+                .nearest_neighbor(&[sample_point.x(), sample_point.y()])
                 .unwrap();
             assert_eq!(nearest.unwrap(), &map_nearest);
         }
     }
+
+    #[test]
+    fn key_value_insert_get_mut() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, String)>::new();
+        map.insert((Point::new(0., 1.), String::from("a"))).unwrap();
+        map.insert((Point::new(1., 0.), String::from("b"))).unwrap();
+
+        assert_eq!(map.items(), 2);
+        assert_eq!(map.get(&[0., 1.]).unwrap().1, "a");
+
+        map.get_mut(&[1., 0.]).unwrap().push('c');
+        assert_eq!(map.get(&[1., 0.]).unwrap().1, "bc");
+        assert_eq!(None, map.get_mut(&[2., 2.]));
+
+        let old = map.insert((Point::new(0., 1.), String::from("d"))).unwrap();
+        assert_eq!(old, Some((Point::new(0., 1.), String::from("a"))));
+        assert_eq!(map.items(), 2);
+    }
+
+    #[test]
+    fn key_value_queries() {
+        let mut data: Vec<(Point<f64>, usize)> = (1..=5)
+            .map(|i| (Point::new(i as f64, i as f64), i))
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, usize)>::new();
+        map.batch_insert(&mut data).unwrap();
+
+        let found = map.range_search(&[1., 1.], &[3.5, 3.]).unwrap();
+        assert_eq!(
+            vec![1, 2, 3],
+            found.iter().map(|(_, v)| *v).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some((Point::new(4., 4.), 4)),
+            map.nearest_neighbor(&[4.1, 3.9])
+        );
+
+        let mut values: Vec<usize> = map.into_iter().map(|(_, v)| v).collect();
+        values.sort();
+        assert_eq!(vec![1, 2, 3, 4, 5], values);
+    }
+
+    #[test]
+    fn nearest_neighbor_where() {
+        let mut data: Vec<(Point<f64>, usize)> = create_random_point_type_points(1000, SEED_1)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (p, i % 50))
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, usize)>::new();
+        map.batch_insert(&mut data.clone()).unwrap();
+
+        let sample_points = create_random_point_type_points(50, SEED_2);
+        for (zone, sample_point) in sample_points.iter().enumerate() {
+            data.sort_by(|a, b| {
+                let da = Euclidean::distance_point(&a.0, sample_point);
+                let db = Euclidean::distance_point(&b.0, sample_point);
+                da.partial_cmp(&db).unwrap()
+            });
+            let expected: Vec<&(Point<f64>, usize)> =
+                data.iter().filter(|(_, z)| *z == zone).collect();
+
+            let nearest = map
+                .nearest_neighbor_where(sample_point, |(_, z)| *z == zone)
+                .unwrap();
+            assert_eq!(expected[0], &nearest);
+
+            let neighbors = map.k_nearest_neighbors_where(sample_point, 5, |(_, z)| *z == zone);
+            assert_eq!(expected[..5].len(), neighbors.len());
+            assert!(expected[..5]
+                .iter()
+                .zip(neighbors.iter())
+                .all(|(a, b)| *a == b));
+        }
+
+        let frozen = map.clone().freeze();
+        let query_point = Point::new(0.5, 0.5);
+        assert_eq!(
+            map.k_nearest_neighbors_where(&query_point, 3, |(p, _)| p.x() < 0.1),
+            frozen.k_nearest_neighbors_where(&query_point, 3, |(p, _)| p.x() < 0.1)
+        );
+        assert_eq!(None, map.nearest_neighbor_where(&query_point, |_| false));
+        assert_eq!(None, frozen.nearest_neighbor_where(&query_point, |_| false));
+    }
+
+    #[test]
+    fn key_value_remove() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, u8)>::new();
+        let a: Point<f64> = Point::new(0., 1.);
+        map.insert((a, 7)).unwrap();
+        assert_eq!(Some((a, 7)), map.remove(&a));
+        assert_eq!(None, map.get(&[0., 1.]));
+    }
+
+    #[test]
+    fn test_k_nearest_neighbors() {
+        let points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points.clone()).unwrap();
+
+        let sample_points = create_random_point_type_points(100, SEED_2);
+        for sample_point in sample_points.iter() {
+            let mut expected = points.clone();
+            expected.sort_by(|a, b| {
+                Euclidean::distance_point(a, sample_point)
+                    .partial_cmp(&Euclidean::distance_point(b, sample_point))
+                    .unwrap()
+            });
+            expected.truncate(10);
+            let map_nearest = map.k_nearest_neighbors(&[sample_point.x(), sample_point.y()], 10);
+            assert_eq!(expected, map_nearest);
+        }
+    }
+
+    #[test]
+    fn k_nearest_neighbors_more_than_items() {
+        let data = vec![[1., 1.], [2., 1.], [3., 2.]];
+        let (map, _points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&data).unwrap();
+        assert_eq!(3, map.k_nearest_neighbors(&[0., 0.], 5).len());
+        assert!(map.k_nearest_neighbors(&[0., 0.], 0).is_empty());
+        assert!(LearnedHashMap::<LinearModel<f64>, f64>::new()
+            .k_nearest_neighbors(&[0., 0.], 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn radius_range() {
+        let mut data: Vec<Point<f64>> = vec![
+            Point::new(0., 0.),
+            Point::new(0.9, 0.9),
+            Point::new(1., 0.),
+            Point::new(0.5, 0.5),
+            Point::new(-1., 0.),
+        ];
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut data).unwrap();
+
+        let mut found = map.radius_range(&[0., 0.], 1.).unwrap();
+        found.sort_by(|a, b| a.x().partial_cmp(&b.x()).unwrap());
+        let expected = vec![
+            Point::new(-1., 0.),
+            Point::new(0., 0.),
+            Point::new(0.5, 0.5),
+            Point::new(1., 0.),
+        ];
+        assert_eq!(expected, found);
+
+        // The corner point is only found with the bounding square
+        assert_eq!(5, map.square_range(&[0., 0.], 1.).unwrap().len());
+        assert_eq!(None, map.radius_range(&[5., 5.], 1.));
+    }
+
+    #[test]
+    fn radius_range_with_distance() {
+        let points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points.clone()).unwrap();
+
+        let query_point = Point::new(0.5, 0.5);
+        let radius = 0.1;
+        let expected = points
+            .iter()
+            .filter(|p| Euclidean::distance_point(p, &query_point) <= radius)
+            .count();
+        let found = map
+            .radius_range_with_distance(&[query_point.x(), query_point.y()], radius)
+            .unwrap();
+        assert_eq!(expected, found.len());
+        for (p, d) in found.iter() {
+            assert_delta!(Euclidean::distance_point(p, &query_point), *d, 0.00001);
+        }
+    }
+
+    fn check_queries_with<Dist: Distance<F = f64>>(radius: f64) {
+        let points = create_random_point_type_points(2000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points.clone()).unwrap();
+
+        for query_point in create_random_point_type_points(20, SEED_2) {
+            let mut distances: Vec<f64> = points
+                .iter()
+                .map(|p| Dist::distance_point(p, &query_point))
+                .collect();
+            distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let neighbors = map.k_nearest_neighbors_with::<Dist, _>(&query_point, 10);
+            let found: Vec<f64> = neighbors
+                .iter()
+                .map(|p| Dist::distance_point(p, &query_point))
+                .collect();
+            assert_eq!(distances[..10], found[..]);
+
+            let expected = distances.iter().filter(|&&d| d <= radius).count();
+            let found = map
+                .radius_range_with::<Dist, _>(&query_point, radius)
+                .unwrap_or_default();
+            assert_eq!(expected, found.len());
+            for (p, d) in found.iter() {
+                assert_eq!(Dist::distance_point(p, &query_point), *d);
+            }
+        }
+    }
+
+    #[test]
+    fn queries_with_distance() {
+        check_queries_with::<Euclidean<f64>>(0.1);
+        check_queries_with::<SquaredEuclidean<f64>>(0.01);
+        check_queries_with::<Manhattan<f64>>(0.1);
+        check_queries_with::<Chebyshev<f64>>(0.1);
+        check_queries_with::<Minkowski<f64, 3>>(0.1);
+    }
+
+    #[test]
+    fn geographic_queries() {
+        // Points spread over most of the globe, away from the antimeridian, and within a city
+        let world: Vec<Point<f64>> = create_random_point_type_points(2000, SEED_1)
+            .iter()
+            .map(|p| Point::new(p.x() * 120. - 60., p.y() * 340. - 170.))
+            .collect();
+        let city: Vec<Point<f64>> = create_random_point_type_points(2000, SEED_1)
+            .iter()
+            .map(|p| Point::new(p.x() * 0.2 - 37.9, p.y() * 0.3 + 144.8))
+            .collect();
+        for (points, radius) in [(world, 1_000_000.), (city, 1_000.)] {
+            for axis in 0..2 {
+                let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+                    .metric(Metric::Haversine)
+                    .axis(axis)
+                    .build()
+                    .unwrap();
+                map.batch_insert(&mut points.clone()).unwrap();
+
+                let (min, max) = (points[0], points[1]);
+                for query_point in [min, max, Point::new(min.x(), max.y())] {
+                    let mut distances: Vec<f64> = points
+                        .iter()
+                        .map(|p| Haversine::distance_point(p, &query_point))
+                        .collect();
+                    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                    let neighbors = map.k_nearest_neighbors(&query_point, 10);
+                    let found: Vec<f64> = neighbors
+                        .iter()
+                        .map(|p| Haversine::distance_point(p, &query_point))
+                        .collect();
+                    assert_eq!(distances[..10], found[..]);
+
+                    let expected = distances.iter().filter(|&&d| d <= radius).count();
+                    let found = map
+                        .radius_range_with_distance(&query_point, radius)
+                        .unwrap_or_default();
+                    assert_eq!(expected, found.len());
+                }
+
+                let frozen = map.clone().freeze();
+                assert_eq!(Metric::Haversine, frozen.config().metric);
+                assert_eq!(
+                    map.k_nearest_neighbors(&points[2], 5),
+                    frozen.k_nearest_neighbors(&points[2], 5)
+                );
+                assert_eq!(Metric::Haversine, frozen.thaw().config().metric);
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_queries() {
+        let points = create_random_point_type_points(2000, SEED_1);
+        // Distance on a cylinder of circumference 1 along the x axis
+        let wrapped = |p: &Point<f64>, q: &Point<f64>| {
+            let dx = (p.x() - q.x()).abs();
+            dx.min(1. - dx).hypot(p.y() - q.y())
+        };
+        for axis in 0..2 {
+            let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+                .periodic(PeriodicAxis::new(0, 0., 1.))
+                .axis(axis)
+                .build()
+                .unwrap();
+            map.batch_insert(&mut points.clone()).unwrap();
+
+            for query_point in [
+                Point::new(0.01, 0.5),
+                Point::new(0.98, 0.3),
+                Point::new(1.5, 0.7),
+            ] {
+                let mut distances: Vec<f64> =
+                    points.iter().map(|p| wrapped(p, &query_point)).collect();
+                distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                let neighbors = map.k_nearest_neighbors(&query_point, 10);
+                let found: Vec<f64> = neighbors.iter().map(|p| wrapped(p, &query_point)).collect();
+                for (a, b) in distances[..10].iter().zip(found.iter()) {
+                    assert_delta!(a, b, 1e-9);
+                }
+
+                let expected = distances.iter().filter(|&&d| d <= 0.1).count();
+                let found = map.radius_range_with_distance(&query_point, 0.1).unwrap();
+                assert_eq!(expected, found.len());
+                for (p, d) in found.iter() {
+                    assert_delta!(wrapped(p, &query_point), d, 1e-9);
+                }
+            }
+
+            // The range from 0.9 to 0.1 crosses the boundary
+            let inside = |p: &&Point<f64>| (p.x() >= 0.9 || p.x() <= 0.1) && p.y() <= 0.5;
+            let expected = points.iter().filter(inside).count();
+            let found = map.range_search(&[0.9, 0.], &[0.1, 0.5]).unwrap();
+            assert_eq!(expected, found.len());
+            assert!(found.iter().all(|p| inside(&p)));
+            // Ranges are moved into the domain, and a range wider than the period covers it all
+            assert_eq!(
+                found.len(),
+                map.range_search(&[1.9, 0.], &[2.1, 0.5]).unwrap().len()
+            );
+            assert_eq!(
+                points.iter().filter(|p| p.y() <= 0.5).count(),
+                map.range_search(&[-0.1, 0.], &[1.1, 0.5]).unwrap().len()
+            );
+
+            let frozen = map.clone().freeze();
+            assert_eq!(
+                map.range_search(&[0.9, 0.], &[0.1, 0.5]),
+                frozen.range_search(&[0.9, 0.], &[0.1, 0.5])
+            );
+            assert_eq!(
+                map.k_nearest_neighbors(&[0.99, 0.5], 10),
+                frozen.k_nearest_neighbors(&[0.99, 0.5], 10)
+            );
+        }
+
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .periodic(PeriodicAxis::new(0, 0., 1.))
+            .build()
+            .unwrap();
+        assert_eq!(Err(Error::OutOfDomain), map.insert(Point::new(1.5, 0.)));
+        assert_eq!(
+            Err(Error::OutOfDomain),
+            map.batch_insert(&mut [Point::new(0.5, 0.), Point::new(-0.5, 0.)])
+        );
+        assert_eq!(Ok(None), map.insert(Point::new(1., 0.)));
+    }
+
+    #[test]
+    fn geographic_queries_across_antimeridian() {
+        let points: Vec<Point<f64>> = create_random_point_type_points(2000, SEED_1)
+            .iter()
+            .map(|p| Point::new(p.x() * 160. - 80., p.y() * 360. - 180.))
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .metric(Metric::Haversine)
+            .axis(1)
+            .build()
+            .unwrap();
+        map.batch_insert(&mut points.clone()).unwrap();
+        assert_eq!(
+            Some(PeriodicAxis::longitude()),
+            map.config().periodic_axis()
+        );
+
+        for query_point in [
+            Point::new(70., 179.),
+            Point::new(-10., -179.5),
+            Point::new(85., 0.),
+        ] {
+            let mut distances: Vec<f64> = points
+                .iter()
+                .map(|p| Haversine::distance_point(p, &query_point))
+                .collect();
+            distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let neighbors = map.k_nearest_neighbors(&query_point, 10);
+            let found: Vec<f64> = neighbors
+                .iter()
+                .map(|p| Haversine::distance_point(p, &query_point))
+                .collect();
+            assert_eq!(distances[..10], found[..]);
+
+            let expected = distances.iter().filter(|&&d| d <= 1_000_000.).count();
+            let found = map
+                .radius_range_with_distance(&query_point, 1_000_000.)
+                .unwrap_or_default();
+            assert_eq!(expected, found.len());
+        }
+    }
+
+    #[test]
+    fn geographic_nearest_across_antimeridian() {
+        let mut points: Vec<Point<f64>> = (0..100)
+            .map(|i| Point::new((i % 10) as f64 - 5., i as f64 * 3.5 - 180.))
+            .collect();
+        let across = Point::new(0., -179.);
+        points.push(across);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .metric(Metric::Haversine)
+            .axis(1)
+            .build()
+            .unwrap();
+        map.batch_insert(&mut points).unwrap();
+
+        // The closest point is 2 degrees away across the antimeridian, the other points are more
+        // than 5 degrees away on the same side
+        let query_point = Point::new(0., 179.);
+        assert_eq!(Some(across), map.nearest_neighbor(&query_point));
+        let found = map.radius_range(&query_point, 300_000.).unwrap();
+        assert_eq!(vec![across], found);
+        assert_eq!(
+            Some(across),
+            map.clone().freeze().nearest_neighbor(&query_point)
+        );
+
+        assert_eq!(Err(Error::OutOfDomain), map.insert(Point::new(0., 190.)));
+    }
+
+    #[test]
+    fn polygon_search() {
+        let points = create_random_point_type_points(2000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points.clone()).unwrap();
+
+        // A concave star with a square hole
+        let star: Vec<[f64; 2]> = (0..10)
+            .map(|i| {
+                let angle = i as f64 * std::f64::consts::PI / 5.;
+                let radius = if i % 2 == 0 { 0.4 } else { 0.15 };
+                [0.5 + radius * angle.cos(), 0.5 + radius * angle.sin()]
+            })
+            .collect();
+        let holes = [[[0.45, 0.45], [0.55, 0.45], [0.55, 0.55], [0.45, 0.55]]];
+        let expected: Vec<&Point<f64>> = points
+            .iter()
+            .filter(|p| polygon_contains(&star, &holes, &p.coords()))
+            .collect();
+        assert!(!expected.is_empty());
+
+        let found = map.polygon_search_with_holes(&star, &holes).unwrap();
+        assert_eq!(expected.len(), found.len());
+        assert!(expected.iter().all(|p| found.contains(p)));
+        let without_hole = map.polygon_search(&star).unwrap();
+        assert!(without_hole.len() > found.len());
+        assert_eq!(
+            map.clone()
+                .freeze()
+                .polygon_search_with_holes(&star, &holes),
+            Some(found)
+        );
+
+        assert_eq!(None, map.polygon_search(&[]));
+        assert_eq!(None, map.polygon_search(&[[2., 2.], [3., 2.], [3., 3.]]));
+    }
+
+    #[test]
+    fn polygon_search_across_periodic_boundary() {
+        let points = create_random_point_type_points(2000, SEED_1);
+        for axis in 0..2 {
+            let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+                .periodic(PeriodicAxis::new(0, 0., 1.))
+                .axis(axis)
+                .build()
+                .unwrap();
+            map.batch_insert(&mut points.clone()).unwrap();
+
+            // A diamond from 0.85 to 1.15 crosses the boundary at 1
+            let diamond = |shift: f64| {
+                [[0.85, 0.5], [1., 0.2], [1.15, 0.5], [1., 0.8]].map(|[x, y]| [x + shift, y])
+            };
+            let expected: Vec<&Point<f64>> = points
+                .iter()
+                .filter(|p| {
+                    [p.x(), p.x() + 1.].iter().any(|&x| {
+                        polygon_contains::<f64, [[f64; 2]; 0]>(&diamond(0.), &[], &[x, p.y()])
+                    })
+                })
+                .collect();
+            assert!(expected.iter().any(|p| p.x() < 0.5));
+            assert!(expected.iter().any(|p| p.x() > 0.5));
+
+            // The points are tested at their images in the polygon moved by whole periods
+            let frozen = map.clone().freeze();
+            for shift in [-2., -1., 0., 3.] {
+                for shifted in [
+                    map.polygon_search(&diamond(shift)).unwrap(),
+                    frozen.polygon_search(&diamond(shift)).unwrap(),
+                ] {
+                    assert_eq!(expected.len(), shifted.len());
+                    assert!(expected.iter().all(|p| shifted.contains(p)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shared_queries_across_threads() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points.clone()).unwrap();
+        let map = Arc::new(map);
+
+        let handles: Vec<_> = points
+            .chunks(250)
+            .map(|chunk| {
+                let map = Arc::clone(&map);
+                let chunk = chunk.to_vec();
+                thread::spawn(move || {
+                    for p in chunk.iter() {
+                        assert_eq!(Some(p), map.get(&[p.x(), p.y()]));
+                        assert_eq!(Some(*p), map.nearest_neighbor(&[p.x(), p.y()]));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    fn assert_clustered_queries<M>(mut map: LearnedHashMap<M, f64>)
+    where
+        M: Model<F = f64> + Default + Clone,
+    {
+        // Clustered points around three centers
+        let mut points: Vec<Point<f64>> = create_random_point_type_points(900, SEED_1)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let center = (i % 3) as f64 * 10.;
+                Point::new(center + p.x() * 0.1, p.y())
+            })
+            .collect();
+        map.batch_insert(&mut points).unwrap();
+
+        for p in points.iter() {
+            assert_eq!(Some(p), map.get(&[p.x(), p.y()]));
+        }
+        let sample_points = create_random_point_type_points(100, SEED_2);
+        for sample_point in sample_points.iter() {
+            let query_point = Point::new(sample_point.x() * 20., sample_point.y());
+            let nearest = points
+                .iter()
+                .min_by(|a, b| {
+                    Euclidean::distance_point(a, &query_point)
+                        .partial_cmp(&Euclidean::distance_point(b, &query_point))
+                        .unwrap()
+                })
+                .unwrap();
+            assert_eq!(
+                Some(*nearest),
+                map.nearest_neighbor(&[query_point.x(), query_point.y()])
+            );
+        }
+    }
+
+    #[test]
+    fn piecewise_model_queries() {
+        use crate::models::PiecewiseModel;
+        assert_clustered_queries(LearnedHashMap::with_hasher(LearnedHasher::with_model(
+            PiecewiseModel::with_epsilon(2.),
+        )));
+    }
+
+    #[test]
+    fn rmi_model_queries() {
+        use crate::models::RmiModel;
+        assert_clustered_queries(LearnedHashMap::with_hasher(LearnedHasher::with_model(
+            RmiModel::with_leaves(8),
+        )));
+    }
+
+    #[test]
+    fn spline_model_queries() {
+        use crate::models::SplineModel;
+        assert_clustered_queries(LearnedHashMap::with_hasher(LearnedHasher::with_model(
+            SplineModel::with_knots(16),
+        )));
+    }
+
+    #[test]
+    fn auto_model_queries() {
+        use crate::models::AutoModel;
+        assert_clustered_queries(LearnedHashMap::with_hasher(LearnedHasher::with_model(
+            AutoModel::new(),
+        )));
+    }
+
+    #[test]
+    fn three_dimensional_queries() {
+        let mut points = create_random_nd_points::<3>(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64, 3>, 3>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        for p in points.iter() {
+            assert_eq!(Some(p), map.get(p.coords()));
+        }
+
+        let bottom_left = [0.2, 0.3, 0.1];
+        let top_right = [0.5, 0.6, 0.4];
+        let mut found = map.range_search(&bottom_left, &top_right).unwrap();
+        let mut expected: Vec<Point<f64, 3>> = points
+            .iter()
+            .filter(|p| (0..3).all(|i| p.coord(i) >= bottom_left[i] && p.coord(i) <= top_right[i]))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.coords().partial_cmp(b.coords()).unwrap());
+        expected.sort_by(|a, b| a.coords().partial_cmp(b.coords()).unwrap());
+        assert_eq!(expected, found);
+
+        let sample_points = create_random_nd_points::<3>(50, SEED_2);
+        for sample_point in sample_points.iter() {
+            let mut expected = points.clone();
+            expected.sort_by(|a, b| {
+                Euclidean::distance_point(a, sample_point)
+                    .partial_cmp(&Euclidean::distance_point(b, sample_point))
+                    .unwrap()
+            });
+            expected.truncate(5);
+            assert_eq!(expected, map.k_nearest_neighbors(sample_point.coords(), 5));
+
+            let radius = 0.15;
+            let in_sphere = points
+                .iter()
+                .filter(|p| Euclidean::distance_point(p, sample_point) <= radius)
+                .count();
+            assert_eq!(
+                in_sphere,
+                map.radius_range(sample_point.coords(), radius)
+                    .map_or(0, |found| found.len())
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_rehash() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        // Points beyond the trained range grow the table without retraining
+        let outside: Vec<Point<f64>> = create_random_point_type_points(100, SEED_2)
+            .into_iter()
+            .map(|p| Point::new(p.x() + 3., p.y() + 3.))
+            .collect();
+        let coefficient = map.hasher.model.coefficient;
+        let n_buckets = map.len();
+        map.insert(outside[0]).unwrap();
+        assert!(map.migration.is_none());
+        assert_eq!(coefficient, map.hasher.model.coefficient);
+        assert!(map.len() > n_buckets);
+        assert_eq!(Some(&outside[0]), map.get(&outside[0]));
+
+        // A retraining moves the entries incrementally
+        map.set_retrain_policy(RetrainPolicy {
+            max_inserts: Some(1),
+            ..Default::default()
+        });
+        map.insert(outside[0]).unwrap();
+        map.set_retrain_policy(RetrainPolicy::never());
+        assert!(map.migration.is_some());
+        assert_ne!(coefficient, map.hasher.model.coefficient);
+
+        // Replace a point that is not migrated yet
+        let migration = map.migration.as_ref().unwrap();
+        let last = *migration
+            .table
+            .iter()
+            .rev()
+            .find_map(|b| b.first())
+            .unwrap();
+        assert_eq!(Some(last), map.insert(last).unwrap());
+        assert_eq!(1001, map.items());
+        assert_eq!(Some(points[0]), map.remove(&points[0]));
+
+        let mut all: Vec<Point<f64>> = points[1..].to_vec();
+        all.push(outside[0]);
+        let check = |map: &LearnedHashMap<LinearModel<f64>, f64>, all: &[Point<f64>]| {
+            assert_eq!(all.len(), map.items());
+            assert_eq!(all.len(), map.iter().count());
+            for p in all.iter() {
+                assert_eq!(Some(p), map.get(p));
+            }
+            let mut found = map.range_search(&[0.2, 0.2], &[3.4, 3.4]).unwrap();
+            let mut expected: Vec<Point<f64>> = all
+                .iter()
+                .filter(|p| p.x() >= 0.2 && p.x() <= 3.4 && p.y() >= 0.2 && p.y() <= 3.4)
+                .cloned()
+                .collect();
+            found.sort_by(|a, b| a.coords().partial_cmp(b.coords()).unwrap());
+            expected.sort_by(|a, b| a.coords().partial_cmp(b.coords()).unwrap());
+            assert_eq!(expected, found);
+
+            let query_point = [1.5, 1.5];
+            let mut expected = all.to_vec();
+            expected.sort_by(|a, b| {
+                Euclidean::distance(a.coords(), &query_point)
+                    .partial_cmp(&Euclidean::distance(b.coords(), &query_point))
+                    .unwrap()
+            });
+            expected.truncate(5);
+            assert_eq!(expected, map.k_nearest_neighbors(&query_point, 5));
+        };
+        check(&map, &all);
+
+        for p in outside[1..].iter() {
+            map.insert(*p).unwrap();
+            all.push(*p);
+        }
+        check(&map, &all);
+        assert_eq!(all.len(), map.clone().into_iter().count());
+        assert_eq!(all.len(), map.clone().freeze().items());
+
+        // The migration completes after enough operations
+        for _ in 0..map.len() {
+            map.remove(&[-1., -1.]);
+        }
+        assert!(map.migration.is_none());
+        check(&map, &all);
+    }
+
+    #[test]
+    fn append_only_inserts() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        let coefficient = map.hasher.model.coefficient;
+
+        // Every insert is a new maximum, the table grows by the growth factor
+        let mut growths = 0;
+        for i in 0..2000 {
+            let n_buckets = map.len();
+            let p = Point::new(1. + i as f64 / 1000., 0.5);
+            map.insert(p).unwrap();
+            assert!(map.migration.is_none());
+            if map.len() != n_buckets {
+                growths += 1;
+            }
+        }
+        assert!(growths <= 4);
+        assert_eq!(coefficient, map.hasher.model.coefficient);
+        assert_eq!(3000, map.items());
+        for i in 0..2000 {
+            let p = Point::new(1. + i as f64 / 1000., 0.5);
+            assert_eq!(Some(&p), map.get(&p));
+        }
+    }
+
+    #[test]
+    fn retrain_policy() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let clustered: Vec<Point<f64>> = create_random_point_type_points(500, SEED_2)
+            .into_iter()
+            .map(|p| Point::new(0.5 + p.x() / 100., 0.5 + p.y() / 100.))
+            .collect();
+
+        let mut fixed = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        fixed.batch_insert(&mut points).unwrap();
+        let mut map = fixed.clone();
+        map.set_retrain_policy(RetrainPolicy {
+            max_bucket_len: Some(8),
+            min_inserts: 16,
+            ..Default::default()
+        });
+        for p in clustered.iter() {
+            fixed.insert(*p).unwrap();
+            map.insert(*p).unwrap();
+            assert_eq!(map.table.probes(), map.probes);
+        }
+        map.finish_migration();
+
+        assert_ne!(fixed.hasher.model.coefficient, map.hasher.model.coefficient);
+        assert!(map.mean_probe_len() < fixed.mean_probe_len());
+        assert_eq!(1500, map.items());
+        for p in points.iter().chain(clustered.iter()) {
+            assert_eq!(Some(p), map.get(p));
+        }
+
+        // Probe lengths are tracked through removes
+        for p in clustered.iter().take(100) {
+            assert_eq!(Some(*p), map.remove(p));
+        }
+        assert_eq!(map.table.probes(), map.probes);
+        assert!(map.mean_probe_len() >= 1.);
+
+        // Retrain after a fixed number of inserts
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.set_retrain_policy(RetrainPolicy {
+            max_inserts: Some(100),
+            ..Default::default()
+        });
+        map.batch_insert(&mut points[..500]).unwrap();
+        let coefficient = map.hasher.model.coefficient;
+        for p in points[500..599].iter() {
+            map.insert(*p).unwrap();
+        }
+        assert_eq!(coefficient, map.hasher.model.coefficient);
+        map.insert(points[599]).unwrap();
+        assert_ne!(coefficient, map.hasher.model.coefficient);
+        assert_eq!(0, map.inserts);
+    }
+
+    #[test]
+    fn builder_config() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64>, 2, 2>::builder()
+            .load_factor(4.)
+            .growth_factor(1.5)
+            .initial_buckets(8)
+            .axis(1)
+            .build()
+            .unwrap();
+        assert_eq!(1, map.hasher.axis());
+
+        for p in points.iter().take(10) {
+            map.insert(*p).unwrap();
+        }
+        assert_eq!(8, map.len());
+        map.batch_insert(&mut points).unwrap();
+        assert_eq!(1, map.hasher.axis());
+        assert!(points.windows(2).all(|w| w[0].y() <= w[1].y()));
+        for p in points.iter() {
+            assert_eq!(Some(p), map.get(p));
+        }
+
+        // The table grows by the growth factor once it is filled beyond the load factor
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::builder()
+            .load_factor(2.)
+            .growth_factor(1.5)
+            .initial_buckets(4)
+            .build()
+            .unwrap();
+        for i in 0..10 {
+            map.insert(Point::new(0., i as f64)).unwrap();
+        }
+        assert_eq!(6, map.len());
+
+        assert_eq!(
+            Some(Error::InvalidConfig),
+            LearnedHashMap::<LinearModel<f64>, f64>::builder()
+                .axis(2)
+                .build()
+                .err()
+        );
+
+        // The geographic mode needs (latitude, longitude) points
+        assert_eq!(
+            Some(Error::InvalidConfig),
+            LearnedHashMap::<LinearModel<f64>, f64, Point<f64, 3>, 3>::builder()
+                .metric(Metric::Haversine)
+                .build()
+                .err()
+        );
+        let geographic = MapConfig {
+            metric: Metric::Haversine,
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(Error::InvalidConfig),
+            LearnedHashMap::<LinearModel<f64>, f64, Point<f64, 3>, 3>::with_config(geographic)
+                .err()
+        );
+        assert!(LearnedHashMap::<LinearModel<f64>, f64>::with_config(geographic).is_ok());
+    }
+
+    #[test]
+    fn approx_lookup() {
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        // Round-trip the points through f32
+        for p in points.iter() {
+            let q = [p.x() as f32 as f64, p.y() as f32 as f64];
+            assert_eq!(Some(p), map.get_approx(&q, 1e-6));
+        }
+        assert_eq!(None, map.get_approx(&[-1., -1.], 0.5));
+
+        // The closest entry within epsilon is returned
+        let p = points[0];
+        let q = [p.x() + 0.01, p.y()];
+        let expected = points
+            .iter()
+            .map(|e| (e, Euclidean::distance(e.coords(), &q)))
+            .filter(|(_, d)| *d <= 0.05)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(e, _)| e);
+        assert_eq!(expected, map.get_approx(&q, 0.05));
+
+        let q = [points[1].x() - 1e-9, points[1].y() + 1e-9];
+        assert_eq!(Some(points[1]), map.remove_approx(&q, 1e-6));
+        assert_eq!(None, map.remove_approx(&q, 1e-6));
+        assert_eq!(999, map.items());
+        assert_eq!(
+            Some(&points[2]),
+            map.clone().freeze().get_approx(&points[2], 0.)
+        );
+    }
+
+    #[test]
+    fn dedup_on_insert() {
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (Point<f64>, u32)>::builder()
+            .dedup_tolerance(0.01)
+            .build()
+            .unwrap();
+        assert_eq!(None, map.insert((Point::new(1., 1.), 1)).unwrap());
+        assert_eq!(None, map.insert((Point::new(2., 1.), 2)).unwrap());
+        assert_eq!(
+            Some((Point::new(1., 1.), 1)),
+            map.insert((Point::new(1.001, 1.), 3)).unwrap()
+        );
+        assert_eq!(2, map.items());
+        assert_eq!(None, map.get(&[1., 1.]));
+        assert_eq!(3, map.get(&[1.001, 1.]).unwrap().1);
+        assert_eq!(
+            Some((Point::new(1.001, 1.), 3)),
+            map.insert((Point::new(1.001, 1.), 4)).unwrap()
+        );
+        assert_eq!(None, map.insert((Point::new(1.1, 1.), 5)).unwrap());
+        assert_eq!(3, map.items());
+    }
+
+    #[test]
+    fn point_like_entries() {
+        let mut tuples: Vec<(f64, f64)> = create_random_points(500, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, (f64, f64)>::new();
+        map.batch_insert(&mut tuples).unwrap();
+
+        for &(x, y) in tuples.iter() {
+            assert_eq!(Some(&(x, y)), map.get(&Point::new(x, y)));
+            assert_eq!(Some(&(x, y)), map.get(&[x, y]));
+        }
+        let query_point = (0.5, 0.5);
+        let nearest = tuples
+            .iter()
+            .min_by(|a, b| {
+                Euclidean::distance(&[a.0, a.1], &[0.5, 0.5])
+                    .partial_cmp(&Euclidean::distance(&[b.0, b.1], &[0.5, 0.5]))
+                    .unwrap()
+            })
+            .unwrap();
+        assert_eq!(Some(*nearest), map.nearest_neighbor(&query_point));
+        assert_eq!(Some(*nearest), map.remove(nearest));
+        assert_eq!(499, map.items());
+    }
+
+    #[cfg(feature = "geo-types")]
+    #[test]
+    fn geo_types_entries() {
+        let mut points: Vec<geo_types::Point<f64>> = create_random_points(500, SEED_1)
+            .into_iter()
+            .map(geo_types::Point::from)
+            .collect();
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, geo_types::Point<f64>>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        for p in points.iter() {
+            assert_eq!(Some(p), map.get(p));
+            assert_eq!(Some(p), map.get(&geo_types::Coord::from(*p)));
+        }
+        let found = map
+            .range_search(
+                &geo_types::coord! { x: 0.2, y: 0.2 },
+                &geo_types::coord! { x: 0.4, y: 0.4 },
+            )
+            .unwrap();
+        assert!(found
+            .iter()
+            .all(|p| p.x() >= 0.2 && p.x() <= 0.4 && p.y() >= 0.2 && p.y() <= 0.4));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut points = create_random_nd_points::<3>(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64, 3>, 3>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        let bytes = bincode::serialize(&map).unwrap();
+        let loaded: LearnedHashMap<LinearModel<f64>, f64, Point<f64, 3>, 3> =
+            bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(map.items(), loaded.items());
+        assert_eq!(map.len(), loaded.len());
+        assert_eq!(map.hasher.axis(), loaded.hasher.axis());
+        assert_eq!(
+            map.hasher.model.coefficient,
+            loaded.hasher.model.coefficient
+        );
+        assert_eq!(map.hasher.model.intercept, loaded.hasher.model.intercept);
+        for p in points.iter() {
+            assert_eq!(Some(p), loaded.get(p));
+        }
+        let query_point = [0.5, 0.5, 0.5];
+        assert_eq!(
+            map.k_nearest_neighbors(&query_point, 5),
+            loaded.k_nearest_neighbors(&query_point, 5)
+        );
+
+        // A geographic map cannot be loaded with 3d points
+        map.config.metric = Metric::Haversine;
+        let bytes = bincode::serialize(&map).unwrap();
+        assert!(
+            bincode::deserialize::<LearnedHashMap<LinearModel<f64>, f64, Point<f64, 3>, 3>>(&bytes)
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_with_payload() {
+        let mut points: Vec<(Point<f64>, String)> = create_random_point_type_points(100, SEED_1)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (p, i.to_string()))
+            .collect();
+        let mut map = LearnedHashMap::<crate::models::AutoModel<f64>, f64, _>::new();
+        map.batch_insert(&mut points).unwrap();
+
+        let bytes = bincode::serialize(&map).unwrap();
+        let mut loaded: LearnedHashMap<crate::models::AutoModel<f64>, f64, (Point<f64>, String)> =
+            bincode::deserialize(&bytes).unwrap();
+
+        for (p, payload) in points.iter() {
+            assert_eq!(Some(payload), loaded.get(p).map(|(_, v)| v));
+        }
+        loaded
+            .insert((Point::new(0.5, 0.5), "new".to_string()))
+            .unwrap();
+        assert_eq!(points.len() + 1, loaded.items());
+    }
 }
@@ -1,20 +1,25 @@
-use crate::geometry::Point;
 use num_traits::float::Float;
 use std::cmp::Ordering;
 
 /// State for store nearest neighbors distances and points in min_heap
-#[derive(Copy, Clone, PartialEq)]
-pub struct NearestNeighborState<F>
+#[derive(Copy, Clone)]
+pub struct NearestNeighborState<F, V>
 where
     F: Float,
 {
     pub distance: F,
-    pub point: Point<F>,
+    pub point: V,
 }
 
-impl<F: Float> Eq for NearestNeighborState<F> {}
+impl<F: Float, V> PartialEq for NearestNeighborState<F, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl<F: Float, V> Eq for NearestNeighborState<F, V> {}
 
-impl<F> PartialOrd for NearestNeighborState<F>
+impl<F, V> PartialOrd for NearestNeighborState<F, V>
 where
     F: Float,
 {
@@ -23,12 +28,15 @@ where
     }
 }
 
-impl<F> Ord for NearestNeighborState<F>
+impl<F, V> Ord for NearestNeighborState<F, V>
 where
     F: Float,
 {
     fn cmp(&self, other: &Self) -> Ordering {
         // We flip the ordering on distance, so the queue becomes a min-heap
-        other.distance.partial_cmp(&self.distance).unwrap_or(Ordering::Equal)
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
     }
 }
@@ -0,0 +1,200 @@
+use crate::{
+    error::Error,
+    geometry::{helper::par_extract_axis, PointLike},
+    hasher::LearnedHasher,
+    map::LearnedHashMap,
+    models::{par_batch_predict, Model, Trainer},
+};
+use core::{fmt::Debug, iter::Sum};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+use rayon::prelude::*;
+
+/// BucketOrder holds the indices of a slice of entries ordered by bucket, the entries of a bucket
+/// keep their order in the slice.
+struct BucketOrder {
+    order: Vec<usize>,
+    buckets: Vec<usize>,
+}
+
+impl BucketOrder {
+    /// Orders the entries by the bucket of their hash, computing the hashes in parallel.
+    fn new<M, F, V, const D: usize>(
+        hasher: &LearnedHasher<M>,
+        entries: &[V],
+        n_buckets: usize,
+    ) -> Self
+    where
+        F: Float + AsPrimitive<u64> + Send + Sync,
+        M: Model<F = F> + Sync,
+        V: PointLike<F, D> + Sync,
+    {
+        let keys = par_extract_axis(entries, hasher.axis());
+        let buckets: Vec<usize> = par_batch_predict(&hasher.model, &keys)
+            .into_par_iter()
+            .map(|prediction| {
+                let hash: u64 = prediction.floor().as_();
+                hash as usize % n_buckets
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        // The sort is stable, which keeps the order of the entries in a bucket
+        order.par_sort_by_key(|&i| buckets[i]);
+        Self { order, buckets }
+    }
+
+    /// Returns the indices of the entries in the given bucket.
+    #[inline]
+    fn bucket(&self, bucket: usize) -> &[usize] {
+        let start = self.order.partition_point(|&i| self.buckets[i] < bucket);
+        let end = self.order.partition_point(|&i| self.buckets[i] <= bucket);
+        &self.order[start..end]
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMap<M, F, V, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum + Send + Sync,
+    M: Model<F = F> + Default + Clone + Sync,
+    V: PointLike<F, D> + Clone + Send + Sync,
+{
+    /// Parallel version of `batch_insert`. The points are checked, sorted and hashed in
+    /// parallel, and the buckets are filled in parallel with the entries partitioned by bucket.
+    /// The resulting map is identical to the map built by `batch_insert`.
+    ///
+    /// # Arguments
+    /// * `ps` - A list of entries
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `batch_insert`, and leaves the map unchanged on error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel, Point};
+    /// let mut points: Vec<Point<f64>> = (0..1000)
+    ///     .map(|i| Point::new(i as f64, (i % 7) as f64))
+    ///     .collect();
+    /// let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+    /// map.par_batch_insert(&mut points).unwrap();
+    ///
+    /// assert_eq!(map.items(), 1000);
+    /// assert!(map.get(&[500., 3.]).is_some());
+    /// ```
+    pub fn par_batch_insert(&mut self, ps: &mut [V]) -> Result<(), Error> {
+        if ps.is_empty() {
+            return Ok(());
+        }
+        if !ps.par_iter().all(|e| self.in_domain(&e.coords())) {
+            return Err(Error::OutOfDomain);
+        }
+        self.finish_migration();
+
+        // Loading data into trainer, and fit the data into a new model
+        let trainer = match self.config.axis {
+            Some(axis) => Trainer::par_with_points_along(ps, axis)?,
+            None => Trainer::par_with_points(ps)?,
+        };
+        let mut model = self.hasher.model.clone();
+        trainer.par_train(&mut model)?;
+
+        let axis = trainer.axis();
+        let max_key = ps
+            .par_iter()
+            .map(|p| p.coord(axis))
+            .reduce(F::neg_infinity, F::max);
+        let (hasher, mut table) = self.hasher_with_table(model, axis, max_key, ps.len())?;
+        self.hasher = hasher;
+
+        // Every bucket takes the entries of the map moved into it, followed by the batch, in the
+        // same order as the sequential refill and insert
+        let entries: Vec<V> = self
+            .table
+            .iter_mut()
+            .flat_map(|bucket| bucket.drain(..))
+            .collect();
+        let moved = BucketOrder::new(&self.hasher, &entries, table.len());
+        let inserted = BucketOrder::new(&self.hasher, ps, table.len());
+        let sort_axis = (axis + 1) % D;
+        let added: usize = table
+            .par_iter_mut()
+            .enumerate()
+            .map(|(index, bucket)| {
+                for &i in moved.bucket(index).iter() {
+                    Self::insert_into_bucket(bucket, entries[i].clone(), sort_axis);
+                }
+                inserted
+                    .bucket(index)
+                    .iter()
+                    .filter(|&&i| {
+                        Self::insert_into_bucket(bucket, ps[i].clone(), sort_axis).is_none()
+                    })
+                    .count()
+            })
+            .sum();
+
+        self.items += added;
+        self.probes = table.probes();
+        self.table = table;
+        self.inserts = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::Point,
+        map::LearnedHashMap,
+        models::{LinearModel, PiecewiseModel},
+        test_utilities::*,
+    };
+
+    #[test]
+    fn par_batch_insert() {
+        let points = create_random_point_type_points(200_000, SEED_1);
+        let mut extra = create_random_point_type_points(1000, SEED_2);
+        // Duplicates of the batch replace the entries of the map
+        extra.extend_from_slice(&points[..100]);
+
+        let mut sequential = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        sequential.batch_insert(&mut points.clone()).unwrap();
+        sequential.batch_insert(&mut extra.clone()).unwrap();
+        let mut parallel = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        parallel.par_batch_insert(&mut points.clone()).unwrap();
+        parallel.par_batch_insert(&mut extra.clone()).unwrap();
+
+        assert_eq!(sequential.hasher.axis(), parallel.hasher.axis());
+        assert_eq!(
+            sequential.hasher.model.coefficient,
+            parallel.hasher.model.coefficient
+        );
+        assert_eq!(
+            sequential.hasher.model.intercept,
+            parallel.hasher.model.intercept
+        );
+        assert_eq!(sequential.items, parallel.items);
+        assert_eq!(sequential.probes, parallel.probes);
+        assert_eq!(sequential.table.len(), parallel.table.len());
+        for (a, b) in sequential.table.iter().zip(parallel.table.iter()) {
+            assert_eq!(a.as_slice(), b.as_slice());
+        }
+
+        let mut points = create_random_point_type_points(10_000, SEED_2);
+        let mut sequential = LearnedHashMap::<PiecewiseModel<f64>, f64, Point<f64>, 2, 2>::new();
+        sequential.batch_insert(&mut points.clone()).unwrap();
+        let mut parallel = LearnedHashMap::<PiecewiseModel<f64>, f64, Point<f64>, 2, 2>::new();
+        parallel.par_batch_insert(&mut points).unwrap();
+        for (a, b) in sequential.table.iter().zip(parallel.table.iter()) {
+            assert_eq!(a.as_slice(), b.as_slice());
+        }
+        assert_eq!(
+            Err(crate::error::Error::NonFiniteCoordinate),
+            parallel.par_batch_insert(&mut [Point::new(f64::NAN, 0.)])
+        );
+        assert_eq!(10_000, parallel.items());
+    }
+}
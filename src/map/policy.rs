@@ -0,0 +1,108 @@
+/// Default minimum number of inserts between two retrainings triggered by bucket skew
+const DEFAULT_MIN_INSERTS: usize = 128;
+
+/// RetrainPolicy decides when a LearnedHashMap retrains its model on all of its entries after an
+/// insert, so that the buckets follow the distribution of the points as it drifts.
+///
+/// Every threshold is optional, and the default policy never retrains. The bucket skew is only
+/// checked after `min_inserts` inserts since the last training, as some skew cannot be removed
+/// by retraining, e.g. many points sharing the same key.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{LearnedHashMap, LinearModel, Point, RetrainPolicy};
+/// let mut map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+/// map.set_retrain_policy(RetrainPolicy {
+///     max_bucket_len: Some(16),
+///     max_inserts: Some(10_000),
+///     ..Default::default()
+/// });
+///
+/// map.insert(Point::new(1., 1.)).unwrap();
+/// assert_eq!(map.retrain_policy().max_bucket_len, Some(16));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetrainPolicy {
+    /// Retrain when an insert makes a bucket longer than the given number of entries
+    pub max_bucket_len: Option<usize>,
+
+    /// Retrain when the mean probe length, the mean length of the bucket of an entry, exceeds
+    /// the given value
+    pub max_mean_probe_len: Option<f64>,
+
+    /// Retrain after the given number of inserts since the last training
+    pub max_inserts: Option<usize>,
+
+    /// Minimum number of inserts since the last training before the bucket skew is checked
+    pub min_inserts: usize,
+}
+
+impl Default for RetrainPolicy {
+    fn default() -> Self {
+        Self {
+            max_bucket_len: None,
+            max_mean_probe_len: None,
+            max_inserts: None,
+            min_inserts: DEFAULT_MIN_INSERTS,
+        }
+    }
+}
+
+impl RetrainPolicy {
+    /// Returns a policy that never retrains
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the map should be retrained after an insert
+    ///
+    /// # Arguments
+    /// * `inserts` - number of inserts since the last training
+    /// * `bucket_len` - length of the bucket of the inserted entry
+    /// * `mean_probe_len` - function computing the mean probe length of the map
+    pub(crate) fn should_retrain<G>(
+        &self,
+        inserts: usize,
+        bucket_len: usize,
+        mean_probe_len: G,
+    ) -> bool
+    where
+        G: FnOnce() -> f64,
+    {
+        if self.max_inserts.is_some_and(|max| inserts >= max) {
+            return true;
+        }
+        if inserts < self.min_inserts {
+            return false;
+        }
+        self.max_bucket_len.is_some_and(|max| bucket_len > max)
+            || self
+                .max_mean_probe_len
+                .is_some_and(|max| mean_probe_len() > max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_retrain() {
+        assert!(!RetrainPolicy::never().should_retrain(usize::MAX, usize::MAX, || f64::MAX));
+
+        let policy = RetrainPolicy {
+            max_bucket_len: Some(8),
+            max_mean_probe_len: Some(2.),
+            max_inserts: Some(1000),
+            min_inserts: 10,
+        };
+        assert!(policy.should_retrain(1000, 1, || 1.));
+        assert!(policy.should_retrain(10, 9, || 1.));
+        assert!(policy.should_retrain(10, 1, || 2.5));
+        assert!(!policy.should_retrain(10, 8, || 2.));
+        // Skew is not checked before min_inserts
+        assert!(!policy.should_retrain(9, 100, || 100.));
+    }
+}
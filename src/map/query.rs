@@ -0,0 +1,645 @@
+use crate::{
+    geometry::{distance::*, polygon_contains, ring_bounds, PointLike},
+    hasher::*,
+    map::{nn::*, Metric, PeriodicAxis},
+    models::Model,
+};
+use core::{cmp::Ordering, ops::Range};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// Read access to the buckets of a learned hash table. The queries are written once against
+/// this trait, and shared by the maps with different storage of the buckets.
+pub(crate) trait Buckets<F, const D: usize> {
+    /// Model of the hasher
+    type Model: Model<F = F> + Default;
+
+    /// Entry yielded by the buckets
+    type Entry<'a>: PointLike<F, D>
+    where
+        Self: 'a;
+
+    /// Returns the hasher that maps the points to the buckets
+    fn hasher(&self) -> &LearnedHasher<Self::Model>;
+
+    /// Returns the number of buckets
+    fn n_buckets(&self) -> usize;
+
+    /// Returns the entries of the bucket at index, for index in 0..n_buckets
+    fn bucket(&self, index: usize) -> impl Iterator<Item = Self::Entry<'_>>;
+}
+
+/// A periodic axis in the float type of the coordinates, see `PeriodicAxis`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Wrap<F> {
+    axis: usize,
+    min: F,
+    max: F,
+    period: F,
+}
+
+impl<F: Float + FromPrimitive> Wrap<F> {
+    /// Returns the periodic axis in the float type, or None if the bounds are out of its range.
+    #[inline]
+    pub(crate) fn new(periodic: &PeriodicAxis) -> Option<Self> {
+        let (min, max) = (F::from_f64(periodic.min)?, F::from_f64(periodic.max)?);
+        Some(Self {
+            axis: periodic.axis,
+            min,
+            max,
+            period: max - min,
+        })
+    }
+
+    /// Moves the coordinate into `min..max` by whole periods.
+    #[inline]
+    fn normalize(&self, x: F) -> F {
+        let offset = (x - self.min) % self.period;
+        let offset = if offset < F::zero() {
+            offset + self.period
+        } else {
+            offset
+        };
+        // Rounding can land on max, which is the same place as min
+        if offset >= self.period {
+            self.min
+        } else {
+            self.min + offset
+        }
+    }
+
+    /// Splits a range along the periodic axis into the ranges within the domain. A range whose
+    /// bottom left is larger than its top right wraps around the boundary.
+    pub(crate) fn split_range<const D: usize>(
+        &self,
+        bottom_left: &[F; D],
+        top_right: &[F; D],
+    ) -> Vec<([F; D], [F; D])> {
+        let mut width = top_right[self.axis] - bottom_left[self.axis];
+        if width < F::zero() {
+            width = width + self.period;
+        }
+        let with_axis = |p: &[F; D], x: F| {
+            let mut p = *p;
+            p[self.axis] = x;
+            p
+        };
+        if width >= self.period {
+            return vec![(
+                with_axis(bottom_left, self.min),
+                with_axis(top_right, self.max),
+            )];
+        }
+        let left = self.normalize(bottom_left[self.axis]);
+        let right = left + width;
+        if right <= self.max {
+            return vec![(with_axis(bottom_left, left), with_axis(top_right, right))];
+        }
+        vec![
+            (with_axis(bottom_left, left), with_axis(top_right, self.max)),
+            (
+                with_axis(bottom_left, self.min),
+                with_axis(top_right, right - self.period),
+            ),
+        ]
+    }
+
+    /// Returns true if the predicate holds for an image of the point, shifted by whole periods
+    /// along the axis, within the extent of the range along the axis. The range is not moved
+    /// into the domain.
+    pub(crate) fn any_image<const D: usize>(
+        &self,
+        p: &[F; D],
+        bottom_left: &[F; D],
+        top_right: &[F; D],
+        mut predicate: impl FnMut(&[F; D]) -> bool,
+    ) -> bool {
+        let (low, high) = (bottom_left[self.axis], top_right[self.axis]);
+        let offset = (p[self.axis] - low) % self.period;
+        let mut image = *p;
+        image[self.axis] = if offset < F::zero() {
+            low + offset + self.period
+        } else {
+            low + offset
+        };
+        while image[self.axis] <= high {
+            if predicate(&image) {
+                return true;
+            }
+            image[self.axis] = image[self.axis] + self.period;
+        }
+        false
+    }
+}
+
+/// An image of a query point, shifted by whole periods along the periodic axis. The image
+/// answers for the entries in its window, the entries closer to it than to the other images
+/// along the axis.
+pub(crate) struct Image<F, const D: usize> {
+    pub(crate) point: [F; D],
+    axis: usize,
+    window: Range<F>,
+}
+
+impl<F: Float + FromPrimitive, const D: usize> Image<F, D> {
+    /// Returns the images of the query point, the point itself if there is no periodic axis, or
+    /// the point moved into the domain and its images one period below and above.
+    pub(crate) fn of(wrap: Option<&Wrap<F>>, query_point: &[F; D]) -> Vec<Self> {
+        let Some(wrap) = wrap else {
+            return vec![Self {
+                point: *query_point,
+                axis: 0,
+                window: F::neg_infinity()..F::infinity(),
+            }];
+        };
+        let x = wrap.normalize(query_point[wrap.axis]);
+        let half = wrap.period / F::from_u8(2).unwrap();
+        // The windows share their bounds, so that every entry has exactly one image
+        let (low, high) = (x - half, x + half);
+        [
+            (x - wrap.period, F::neg_infinity()..low),
+            (x, low..high),
+            (x + wrap.period, high..F::infinity()),
+        ]
+        .into_iter()
+        .map(|(x, window)| {
+            let mut point = *query_point;
+            point[wrap.axis] = x;
+            Self {
+                point,
+                axis: wrap.axis,
+                window,
+            }
+        })
+        .collect()
+    }
+
+    /// Returns true if the image answers for the entry at the given point.
+    #[inline]
+    pub(crate) fn answers(&self, p: &[F; D]) -> bool {
+        self.window.contains(&p[self.axis])
+    }
+}
+
+/// Returns the entry with the given point, if it exists in the buckets.
+#[inline]
+pub(crate) fn find<'a, B, F, const D: usize>(buckets: &'a B, p: &[F; D]) -> Option<B::Entry<'a>>
+where
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+{
+    let hash = make_hash_point(buckets.hasher(), p);
+    if hash >= buckets.n_buckets() as u64 {
+        return None;
+    }
+    buckets.bucket(hash as usize).find(|e| e.coords() == *p)
+}
+
+/// Visits all the entries within the given range, by scanning the buckets between the hash of
+/// the bottom left and the hash of the top right of the range.
+///
+/// # Arguments
+/// * `bottom_left` - bottom left of the range
+/// * `top_right` - top right of the range
+/// * `visit` - function called with every entry within the range
+#[inline]
+pub(crate) fn visit_range<'a, B, F, G, const D: usize>(
+    buckets: &'a B,
+    bottom_left: &[F; D],
+    top_right: &[F; D],
+    mut visit: G,
+) where
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+    G: FnMut(B::Entry<'a>),
+{
+    let n_buckets = buckets.n_buckets();
+    if n_buckets == 0 {
+        return;
+    }
+    let mut right_hash = make_hash_point(buckets.hasher(), top_right) as usize;
+    if right_hash >= n_buckets {
+        right_hash = n_buckets - 1;
+    }
+    let left_hash = make_hash_point(buckets.hasher(), bottom_left) as usize;
+    if left_hash >= n_buckets || left_hash > right_hash {
+        return;
+    }
+    for i in left_hash..=right_hash {
+        for e in buckets.bucket(i) {
+            let item = e.coords();
+            let inside = (0..D).all(|i| item[i] >= bottom_left[i] && item[i] <= top_right[i]);
+            if inside {
+                visit(e);
+            }
+        }
+    }
+}
+
+/// Visits all the entries within the given radius of the query point, measured by the distance
+/// Dist, along with their distance to the query point.
+///
+/// The search starts at the bucket of the query point, then expands to the left and right
+/// buckets. The expansion on each side stops as soon as the lower bound of the distance to the
+/// boundary of the next bucket exceeds the radius.
+///
+/// # Arguments
+/// * `query_point` - center of the radius
+/// * `radius` - A radius value
+/// * `visit` - function called with every entry within the radius and its distance
+#[inline]
+pub(crate) fn visit_radius<'a, Dist, B, F, G, const D: usize>(
+    buckets: &'a B,
+    query_point: &[F; D],
+    radius: F,
+    mut visit: G,
+) where
+    Dist: Distance<F = F>,
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+    G: FnMut(B::Entry<'a>, F),
+{
+    if buckets.n_buckets() == 0 {
+        return;
+    }
+    let last_hash = buckets.n_buckets() as u64 - 1;
+    let hash = make_hash_point(buckets.hasher(), query_point).min(last_hash);
+    let mut visit_bucket = |hash: u64| {
+        for e in buckets.bucket(hash as usize) {
+            let d = Dist::distance(query_point, &e.coords());
+            if d <= radius {
+                visit(e, d);
+            }
+        }
+    };
+
+    visit_bucket(hash);
+    for left_hash in (0..hash).rev() {
+        let d = boundary_distance::<Dist, _, _, D>(buckets, query_point, left_hash + 1);
+        if d > radius {
+            break;
+        }
+        visit_bucket(left_hash);
+    }
+    for right_hash in hash + 1..=last_hash {
+        let d = boundary_distance::<Dist, _, _, D>(buckets, query_point, right_hash);
+        if d > radius {
+            break;
+        }
+        visit_bucket(right_hash);
+    }
+}
+
+/// Returns the closest entry within epsilon of the point, measured by Euclidean distance, along
+/// with its distance to the point. The buckets covering the keys within epsilon of the key of
+/// the point are scanned.
+#[inline]
+pub(crate) fn find_approx<'a, B, F, const D: usize>(
+    buckets: &'a B,
+    p: &[F; D],
+    epsilon: F,
+) -> Option<(B::Entry<'a>, F)>
+where
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+{
+    let mut closest: Option<(B::Entry<'a>, F)> = None;
+    visit_radius::<Euclidean<F>, _, _, _, D>(buckets, p, epsilon, |e, d| {
+        if closest.as_ref().is_none_or(|&(_, closest)| d < closest) {
+            closest = Some((e, d));
+        }
+    });
+    closest
+}
+
+/// Find the distances between query point and cadidates neighbors in local bucket, measured by
+/// the distance Dist, then keep the k closest cadidates neighbors in the max_heap.
+///
+/// # Arguments
+/// * `heap` - mutable borrow of an BinaryHeap, ordered by the largest distance on top
+/// * `local_hash` - A hash index of local bucket
+/// * `query_point` - A Point data
+/// * `k` - number of neighbors to keep
+/// * `filter` - predicate the candidates must satisfy
+#[inline]
+fn local_max_heap<'a, Dist, B, F, G, const D: usize>(
+    buckets: &'a B,
+    heap: &mut BinaryHeap<Reverse<NearestNeighborState<F, B::Entry<'a>>>>,
+    local_hash: u64,
+    query_point: &[F; D],
+    k: usize,
+    filter: &mut G,
+) where
+    Dist: Distance<F = F>,
+    B: Buckets<F, D>,
+    F: Float,
+    G: FnMut(&B::Entry<'a>) -> bool,
+{
+    for e in buckets.bucket(local_hash as usize).filter(|e| filter(e)) {
+        let d = Dist::distance(query_point, &e.coords());
+        if heap.len() < k {
+            heap.push(Reverse(NearestNeighborState {
+                distance: d,
+                point: e,
+            }));
+        } else if let Some(mut top) = heap.peek_mut() {
+            // Replace the current k-th nearest neighbor if the candidate is closer
+            if d < top.0.distance {
+                *top = Reverse(NearestNeighborState {
+                    distance: d,
+                    point: e,
+                });
+            }
+        }
+    }
+}
+
+/// Returns the k-th nearest distance in the heap, or the max value of F if the heap holds
+/// less than k neighbors.
+#[inline]
+fn kth_distance<F: Float, V>(
+    heap: &BinaryHeap<Reverse<NearestNeighborState<F, V>>>,
+    k: usize,
+) -> F {
+    match heap.peek() {
+        Some(top) if heap.len() == k => top.0.distance,
+        _ => F::max_value(),
+    }
+}
+
+/// Calculates the lower bound of the distance Dist between query_point and the boundary of the
+/// bucket at index with given hash, see `Distance::lower_bound`.
+///
+/// # Arguments
+/// * `query_point` - A Point data
+/// * `hash` - A hash index of the bucket
+#[inline]
+fn boundary_distance<Dist, B, F, const D: usize>(buckets: &B, query_point: &[F; D], hash: u64) -> F
+where
+    Dist: Distance<F = F>,
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+{
+    let hasher = buckets.hasher();
+    let x: F = unhash(hasher, hash);
+    Dist::lower_bound(query_point, hasher.axis(), x)
+}
+
+/// K nearest neighbors search for the k closest entries satisfying the filter for given query
+/// point, measured by the distance Dist, paired with their distance and ordered by distance.
+///
+/// The search starts at the bucket of the query point, then expands to the left and right
+/// buckets. The expansion on each side stops as soon as the k-th nearest distance is not larger
+/// than the lower bound of the distance to the boundary of the next bucket.
+pub(crate) fn k_nearest_neighbors<'a, Dist, B, F, G, const D: usize>(
+    buckets: &'a B,
+    query_point: &[F; D],
+    k: usize,
+    mut filter: G,
+) -> Vec<(B::Entry<'a>, F)>
+where
+    Dist: Distance<F = F>,
+    B: Buckets<F, D>,
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+    G: FnMut(&B::Entry<'a>) -> bool,
+{
+    if k == 0 || buckets.n_buckets() == 0 {
+        return Vec::new();
+    }
+    let last_hash = buckets.n_buckets() as u64 - 1;
+    // if hash out of max bound, still search right most bucket
+    let hash = make_hash_point(buckets.hasher(), query_point).min(last_hash);
+
+    let mut heap = BinaryHeap::with_capacity(k);
+
+    // Searching at current hash index
+    local_max_heap::<Dist, _, _, _, D>(buckets, &mut heap, hash, query_point, k, &mut filter);
+
+    // Iterate over left, left hash must >= 0
+    let mut left_hash = hash;
+    while left_hash > 0 {
+        left_hash -= 1;
+        // The closest point in the left bucket is bounded by the right boundary of the
+        // bucket, which is the unhashed value of the next hash
+        let left_hash_d = boundary_distance::<Dist, _, _, D>(buckets, query_point, left_hash + 1);
+        if left_hash_d >= kth_distance(&heap, k) {
+            break;
+        }
+        local_max_heap::<Dist, _, _, _, D>(
+            buckets,
+            &mut heap,
+            left_hash,
+            query_point,
+            k,
+            &mut filter,
+        );
+    }
+
+    // Iterate over right
+    let mut right_hash = hash + 1;
+    while right_hash <= last_hash {
+        // Unhash the right_hash, then calculate the lower bound of the distance between
+        // right hash point and query point
+        let right_hash_d = boundary_distance::<Dist, _, _, D>(buckets, query_point, right_hash);
+        if right_hash_d >= kth_distance(&heap, k) {
+            break;
+        }
+        local_max_heap::<Dist, _, _, _, D>(
+            buckets,
+            &mut heap,
+            right_hash,
+            query_point,
+            k,
+            &mut filter,
+        );
+        right_hash += 1;
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(state)| (state.point, state.distance))
+        .collect()
+}
+
+/// The front end of the queries shared by the maps, over the buckets of a map and the buckets
+/// being migrated into it, if any. The queries measure by the metric of the map, wrap around the
+/// periodic axis of the map, and merge the entries of both sets of buckets.
+pub(crate) struct Query<'a, F, B, R, const D: usize> {
+    buckets: &'a B,
+    migration: Option<&'a R>,
+    metric: Metric,
+    wrap: Option<Wrap<F>>,
+}
+
+impl<'a, F, B, R, const D: usize> Query<'a, F, B, R, D>
+where
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+    B: Buckets<F, D> + 'a,
+    R: Buckets<F, D, Entry<'a> = B::Entry<'a>> + 'a,
+{
+    /// Returns the front end of the queries over the buckets and the buckets being migrated.
+    #[inline]
+    pub(crate) fn new(
+        buckets: &'a B,
+        migration: Option<&'a R>,
+        metric: Metric,
+        periodic: Option<PeriodicAxis>,
+    ) -> Self {
+        Self {
+            buckets,
+            migration,
+            metric,
+            wrap: periodic.as_ref().and_then(Wrap::new),
+        }
+    }
+
+    /// Visits all the entries within the given range. A range whose bottom left is larger than
+    /// its top right along the periodic axis wraps around the boundary of the domain.
+    pub(crate) fn visit_range<G>(&self, bottom_left: &[F; D], top_right: &[F; D], mut visit: G)
+    where
+        G: FnMut(B::Entry<'a>),
+    {
+        let ranges = match &self.wrap {
+            Some(wrap) => wrap.split_range(bottom_left, top_right),
+            None => vec![(*bottom_left, *top_right)],
+        };
+        for (bottom_left, top_right) in ranges.iter() {
+            visit_range(self.buckets, bottom_left, top_right, &mut visit);
+            if let Some(migration) = self.migration {
+                visit_range(migration, bottom_left, top_right, &mut visit);
+            }
+        }
+    }
+
+    /// Returns all the entries within the given range, see `visit_range`.
+    #[inline]
+    pub(crate) fn range(&self, bottom_left: &[F; D], top_right: &[F; D]) -> Vec<B::Entry<'a>> {
+        let mut result = Vec::new();
+        self.visit_range(bottom_left, top_right, |e| result.push(e));
+        result
+    }
+
+    /// Returns all the entries within the given radius of the query point measured by the
+    /// metric, paired with their distance.
+    #[inline]
+    pub(crate) fn radius(&self, query_point: &[F; D], radius: F) -> Vec<(B::Entry<'a>, F)> {
+        match self.metric {
+            Metric::Euclidean => self.radius_with::<Euclidean<F>>(query_point, radius),
+            Metric::Haversine => self.radius_with::<Haversine<F>>(query_point, radius),
+        }
+    }
+
+    /// Returns all the entries within the given radius of the query point measured by the
+    /// distance Dist, paired with their distance. Each entry is measured from the image of the
+    /// query point that answers for it.
+    pub(crate) fn radius_with<Dist>(
+        &self,
+        query_point: &[F; D],
+        radius: F,
+    ) -> Vec<(B::Entry<'a>, F)>
+    where
+        Dist: Distance<F = F>,
+    {
+        let mut result = Vec::new();
+        for image in Image::of(self.wrap.as_ref(), query_point) {
+            let mut visit = |e: B::Entry<'a>, d| {
+                if image.answers(&e.coords()) {
+                    result.push((e, d))
+                }
+            };
+            visit_radius::<Dist, _, _, _, D>(self.buckets, &image.point, radius, &mut visit);
+            if let Some(migration) = self.migration {
+                visit_radius::<Dist, _, _, _, D>(migration, &image.point, radius, visit);
+            }
+        }
+        result
+    }
+
+    /// Returns the k closest entries satisfying the filter to the query point measured by the
+    /// metric, ordered by distance.
+    #[inline]
+    pub(crate) fn nearest<G>(&self, query_point: &[F; D], k: usize, filter: G) -> Vec<B::Entry<'a>>
+    where
+        G: FnMut(&B::Entry<'a>) -> bool,
+    {
+        match self.metric {
+            Metric::Euclidean => self.nearest_with::<Euclidean<F>, G>(query_point, k, filter),
+            Metric::Haversine => self.nearest_with::<Haversine<F>, G>(query_point, k, filter),
+        }
+    }
+
+    /// Returns the k closest entries satisfying the filter to the query point measured by the
+    /// distance Dist, ordered by distance. The neighbors of every image of the query point and
+    /// of both sets of buckets are merged.
+    pub(crate) fn nearest_with<Dist, G>(
+        &self,
+        query_point: &[F; D],
+        k: usize,
+        mut filter: G,
+    ) -> Vec<B::Entry<'a>>
+    where
+        Dist: Distance<F = F>,
+        G: FnMut(&B::Entry<'a>) -> bool,
+    {
+        let images = Image::of(self.wrap.as_ref(), query_point);
+        let mut neighbors = Vec::new();
+        for image in images.iter() {
+            let mut filter = |e: &B::Entry<'a>| image.answers(&e.coords()) && filter(e);
+            neighbors.extend(k_nearest_neighbors::<Dist, _, _, _, D>(
+                self.buckets,
+                &image.point,
+                k,
+                &mut filter,
+            ));
+            if let Some(migration) = self.migration {
+                neighbors.extend(k_nearest_neighbors::<Dist, _, _, _, D>(
+                    migration,
+                    &image.point,
+                    k,
+                    filter,
+                ));
+            }
+        }
+        if images.len() > 1 || self.migration.is_some() {
+            neighbors.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            neighbors.truncate(k);
+        }
+        neighbors.into_iter().map(|(e, _)| e).collect()
+    }
+}
+
+impl<'a, F, B, R> Query<'a, F, B, R, 2>
+where
+    F: Float + FromPrimitive + AsPrimitive<u64>,
+    B: Buckets<F, 2> + 'a,
+    R: Buckets<F, 2, Entry<'a> = B::Entry<'a>> + 'a,
+{
+    /// Returns all the entries inside the polygon and outside of its holes, scanning the
+    /// buckets of its bounding box. With a periodic axis, the entries are tested at their
+    /// images shifted by whole periods into the extent of the polygon.
+    pub(crate) fn polygon<H>(&self, exterior: &[[F; 2]], holes: &[H]) -> Vec<B::Entry<'a>>
+    where
+        H: AsRef<[[F; 2]]>,
+    {
+        let Some((bottom_left, top_right)) = ring_bounds(exterior) else {
+            return Vec::new();
+        };
+        let mut result = Vec::new();
+        self.visit_range(&bottom_left, &top_right, |e| {
+            let p = e.coords();
+            let inside = match &self.wrap {
+                Some(wrap) => wrap.any_image(&p, &bottom_left, &top_right, |p| {
+                    polygon_contains(exterior, holes, p)
+                }),
+                None => polygon_contains(exterior, holes, &p),
+            };
+            if inside {
+                result.push(e);
+            }
+        });
+        result
+    }
+}
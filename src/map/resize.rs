@@ -0,0 +1,115 @@
+use crate::{
+    geometry::PointLike,
+    hasher::{make_hash_point, LearnedHasher},
+    map::{query::Buckets, table::Table},
+    models::Model,
+};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+
+/// Number of buckets of the old table migrated by every insert or remove
+pub(crate) const MIGRATION_STEP: usize = 16;
+
+/// Migration holds the table of a LearnedHashMap that is being rehashed incrementally, together
+/// with the hasher its entries were placed with. The buckets are migrated to the new table in
+/// order, the buckets before `next` are already empty.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Migration<M, V, const N: usize = 6> {
+    pub hasher: LearnedHasher<M>,
+    pub table: Table<V, N>,
+    pub next: usize,
+}
+
+impl<M, V, const N: usize> Migration<M, V, N> {
+    /// Returns a migration of all the buckets of the table
+    #[inline]
+    pub fn new(hasher: LearnedHasher<M>, table: Table<V, N>) -> Self {
+        Self {
+            hasher,
+            table,
+            next: 0,
+        }
+    }
+
+    /// Returns true if all the buckets are migrated
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.next >= self.table.len()
+    }
+
+    /// Takes the entries of the next n buckets out of the old table
+    #[inline]
+    pub fn take_buckets(&mut self, n: usize) -> impl Iterator<Item = V> + '_ {
+        let end = self.next.saturating_add(n).min(self.table.len());
+        let start = std::mem::replace(&mut self.next, end);
+        self.table[start..end]
+            .iter_mut()
+            .flat_map(|bucket| bucket.drain(..))
+    }
+
+    /// Returns the index of the bucket of the point in the old table, if it is in the table
+    #[inline]
+    fn index<F, const D: usize>(&self, p: &[F; D]) -> Option<usize>
+    where
+        F: Float + FromPrimitive + AsPrimitive<u64>,
+        M: Model<F = F> + Default,
+    {
+        let hash = make_hash_point(&self.hasher, p);
+        (hash < self.table.len() as u64).then_some(hash as usize)
+    }
+
+    /// Removes the entry with the given point from the old table
+    #[inline]
+    pub fn remove<F, const D: usize>(&mut self, p: &[F; D]) -> Option<V>
+    where
+        F: Float + FromPrimitive + AsPrimitive<u64>,
+        M: Model<F = F> + Default,
+        V: PointLike<F, D>,
+    {
+        let index = self.index(p)?;
+        self.table.remove_entry(index as u64, |e| e.coords() == *p)
+    }
+
+    /// Returns the entry with the given point in the old table
+    #[inline]
+    pub fn get_mut<F, const D: usize>(&mut self, p: &[F; D]) -> Option<&mut V>
+    where
+        F: Float + FromPrimitive + AsPrimitive<u64>,
+        M: Model<F = F> + Default,
+        V: PointLike<F, D>,
+    {
+        let index = self.index(p)?;
+        self.table[index].iter_mut().find(|e| e.coords() == *p)
+    }
+}
+
+impl<M, F, V, const D: usize, const N: usize> Buckets<F, D> for Migration<M, V, N>
+where
+    F: Float,
+    M: Model<F = F> + Default,
+    V: PointLike<F, D>,
+{
+    type Model = M;
+    type Entry<'a>
+        = &'a V
+    where
+        Self: 'a;
+
+    #[inline]
+    fn hasher(&self) -> &LearnedHasher<M> {
+        &self.hasher
+    }
+
+    #[inline]
+    fn n_buckets(&self) -> usize {
+        self.table.len()
+    }
+
+    #[inline]
+    fn bucket(&self, index: usize) -> impl Iterator<Item = &V> {
+        self.table[index].iter()
+    }
+}
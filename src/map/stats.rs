@@ -0,0 +1,191 @@
+use crate::{
+    geometry::PointLike,
+    map::LearnedHashMap,
+    models::{root_mean_squared_error, Model},
+};
+use core::{fmt::Debug, iter::Sum};
+use num_traits::{
+    cast::{AsPrimitive, FromPrimitive},
+    float::Float,
+};
+
+/// MapStats describes the bucket occupancy of a LearnedHashMap and the quality of its model, see
+/// `LearnedHashMap::stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapStats<M, F> {
+    /// Number of buckets of the table
+    pub n_buckets: usize,
+
+    /// Number of entries in the map
+    pub items: usize,
+
+    /// Number of entries not yet moved out of a table being migrated, which are not counted in
+    /// the bucket statistics
+    pub migrating: usize,
+
+    /// Number of empty buckets
+    pub empty_buckets: usize,
+
+    /// Ratio of empty buckets to all buckets, 0 for a table without buckets
+    pub empty_bucket_ratio: f64,
+
+    /// Number of buckets by bucket length, `bucket_histogram[i]` buckets hold `i` entries
+    pub bucket_histogram: Vec<usize>,
+
+    /// Length of the longest bucket
+    pub max_bucket_len: usize,
+
+    /// Mean length of the non-empty buckets
+    pub mean_bucket_len: f64,
+
+    /// Mean probe length, the mean length of the bucket of an entry
+    pub mean_probe_len: f64,
+
+    /// Axis the model is trained along
+    pub axis: usize,
+
+    /// The model of the hasher, holding the trained parameters
+    pub model: M,
+
+    /// Root mean squared error of the model predicting the rank of every stored key along the
+    /// axis, 0 for an empty map
+    pub rmse: F,
+
+    /// Number of bytes owned by the map on the heap, that is the tables and the models. Heap
+    /// data owned by the entries themselves is not counted.
+    pub heap_bytes: usize,
+}
+
+impl<M, F, V, const D: usize, const N: usize> LearnedHashMap<M, F, V, D, N>
+where
+    F: Float + Default + AsPrimitive<u64> + FromPrimitive + Debug + Sum,
+    M: Model<F = F> + Default + Clone,
+    V: PointLike<F, D> + Clone,
+{
+    /// Returns the bucket occupancy and model quality statistics of the map. This scans all
+    /// the buckets and entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsph::{LearnedHashMap, LinearModel};
+    /// let point_data = vec![[1., 1.], [2., 1.], [3., 2.], [4., 4.]];
+    /// let (map, points) = LearnedHashMap::<LinearModel<f64>, f64>::with_data(&point_data).unwrap();
+    ///
+    /// let stats = map.stats();
+    /// assert_eq!(stats.items, 4);
+    /// assert_eq!(stats.bucket_histogram.iter().sum::<usize>(), stats.n_buckets);
+    /// assert!(stats.rmse < 1.);
+    /// ```
+    pub fn stats(&self) -> MapStats<M, F> {
+        let mut bucket_histogram = Vec::new();
+        for bucket in self.table.iter() {
+            if bucket.len() >= bucket_histogram.len() {
+                bucket_histogram.resize(bucket.len() + 1, 0);
+            }
+            bucket_histogram[bucket.len()] += 1;
+        }
+        let n_buckets = self.table.len();
+        let empty_buckets = bucket_histogram.first().copied().unwrap_or(0);
+        let migrating = self.migration.as_ref().map_or(0, |m| m.table.items());
+        let ratio = |a: usize, b: usize| if b == 0 { 0. } else { a as f64 / b as f64 };
+
+        // The model is trained to predict the rank of the keys along the axis
+        let axis = self.hasher.axis();
+        let mut keys: Vec<F> = self.iter().map(|e| e.coord(axis)).collect();
+        keys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let ranks: Vec<F> = (0..keys.len()).map(|i| F::from_usize(i).unwrap()).collect();
+        let rmse = if keys.is_empty() {
+            F::zero()
+        } else {
+            root_mean_squared_error(&ranks, &self.hasher.model.batch_predict(&keys))
+        };
+
+        let heap_bytes = self.table.heap_size()
+            + self.hasher.model.heap_size()
+            + self
+                .migration
+                .as_ref()
+                .map_or(0, |m| m.table.heap_size() + m.hasher.model.heap_size());
+
+        MapStats {
+            n_buckets,
+            items: self.items,
+            migrating,
+            empty_buckets,
+            empty_bucket_ratio: ratio(empty_buckets, n_buckets),
+            max_bucket_len: bucket_histogram.len().saturating_sub(1),
+            mean_bucket_len: ratio(self.items - migrating, n_buckets - empty_buckets),
+            mean_probe_len: self.mean_probe_len(),
+            bucket_histogram,
+            axis,
+            model: self.hasher.model.clone(),
+            rmse,
+            heap_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::Point,
+        map::LearnedHashMap,
+        models::{LinearModel, PiecewiseModel},
+        test_utilities::*,
+    };
+
+    #[test]
+    fn stats() {
+        let map = LearnedHashMap::<LinearModel<f64>, f64>::new();
+        let stats = map.stats();
+        assert_eq!(0, stats.n_buckets);
+        assert_eq!(0., stats.empty_bucket_ratio);
+        assert_eq!(0., stats.rmse);
+        assert_eq!(0, stats.heap_bytes);
+
+        let mut points = create_random_point_type_points(1000, SEED_1);
+        let mut map = LearnedHashMap::<LinearModel<f64>, f64, Point<f64>, 2, 2>::new();
+        map.batch_insert(&mut points).unwrap();
+        let stats = map.stats();
+        let lens: Vec<usize> = map.table.iter().map(|b| b.len()).collect();
+
+        assert_eq!(map.len(), stats.n_buckets);
+        assert_eq!(1000, stats.items);
+        assert_eq!(0, stats.migrating);
+        assert_eq!(
+            lens.iter().filter(|&&l| l == 0).count(),
+            stats.empty_buckets
+        );
+        assert_eq!(*lens.iter().max().unwrap(), stats.max_bucket_len);
+        assert_eq!(
+            1000,
+            stats
+                .bucket_histogram
+                .iter()
+                .enumerate()
+                .map(|(len, n)| len * n)
+                .sum::<usize>()
+        );
+        assert_eq!(
+            1000. / (stats.n_buckets - stats.empty_buckets) as f64,
+            stats.mean_bucket_len
+        );
+        assert_eq!(map.hasher.model.coefficient, stats.model.coefficient);
+        assert!(stats.rmse > 0. && stats.rmse < 100.);
+
+        // Buckets longer than the inline capacity own their entries on the heap
+        let spilled: usize = lens.iter().filter(|&&l| l > 2).sum();
+        assert!(
+            stats.heap_bytes
+                >= map.len() * core::mem::size_of_val(&map.table[0])
+                    + spilled * core::mem::size_of::<Point<f64>>()
+        );
+
+        // The heap of the model is counted
+        let mut map = LearnedHashMap::<PiecewiseModel<f64>, f64>::new();
+        map.batch_insert(&mut points).unwrap();
+        let stats = map.stats();
+        assert!(stats.heap_bytes > map.table.heap_size());
+    }
+}
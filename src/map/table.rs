@@ -1,13 +1,19 @@
-use core::ops::{Deref, DerefMut};
+use crate::error::Error;
+use core::{
+    mem,
+    ops::{Deref, DerefMut},
+};
 use smallvec::SmallVec;
 
-/// Bucket is the lower unit in the HashMap to store the points
+/// Bucket is the lower unit in the HashMap to store the points, the first N entries are stored
+/// inline without allocation
 #[derive(Debug, Clone)]
-pub(crate) struct Bucket<V> {
-    entry: SmallVec<[V; 6]>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Bucket<V, const N: usize = 6> {
+    entry: SmallVec<[V; N]>,
 }
 
-impl<V> Bucket<V> {
+impl<V, const N: usize> Bucket<V, N> {
     /// Returns a default Bucket with value type.
     #[inline]
     pub fn new() -> Self {
@@ -24,14 +30,14 @@ impl<V> Bucket<V> {
     }
 }
 
-impl<V> Deref for Bucket<V> {
-    type Target = SmallVec<[V; 6]>;
+impl<V, const N: usize> Deref for Bucket<V, N> {
+    type Target = SmallVec<[V; N]>;
     fn deref(&self) -> &Self::Target {
         &self.entry
     }
 }
 
-impl<V> DerefMut for Bucket<V> {
+impl<V, const N: usize> DerefMut for Bucket<V, N> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.entry
     }
@@ -39,11 +45,12 @@ impl<V> DerefMut for Bucket<V> {
 
 /// Table containing a Vec of Bucket to store the values
 #[derive(Debug, Clone)]
-pub(crate) struct Table<V> {
-    buckets: Vec<Bucket<V>>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Table<V, const N: usize = 6> {
+    buckets: Vec<Bucket<V, N>>,
 }
 
-impl<V> Table<V> {
+impl<V, const N: usize> Table<V, N> {
     /// Returns a default Table with empty Vec.
     #[inline]
     pub fn new() -> Self {
@@ -63,10 +70,18 @@ impl<V> Table<V> {
         }
     }
 
-    /// Returns the capacity of the Table.
+    /// Returns a Table with Vec that with the given capacity, or Error::CapacityOverflow if the
+    /// capacity cannot be allocated.
+    ///
+    /// # Arguments
+    /// * `capacity` - A capacity size for the Table
     #[inline]
-    pub fn capacity(&self) -> usize {
-        self.buckets.capacity()
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, Error> {
+        let mut buckets = Vec::new();
+        buckets
+            .try_reserve_exact(capacity)
+            .map_err(|_| Error::CapacityOverflow)?;
+        Ok(Self { buckets })
     }
 
     /// Returns the Bucket with given hash value.
@@ -77,33 +92,60 @@ impl<V> Table<V> {
     pub fn bucket(&self, hash: u64) -> usize {
         hash as usize % self.buckets.len()
     }
-}
-impl<V> Table<V>
-where
-    V: PartialEq,
-{
-    /// Remove entry with given hash value and entry.
+
+    /// Remove the first entry matching the predicate in the bucket with given hash value.
     ///
     /// # Arguments
     /// * `hash` - A hash value for indexing the bucket in the table
-    /// * `entry` - Entry to remove
+    /// * `predicate` - Predicate matching the entry to remove
     #[inline]
-    pub fn remove_entry(&mut self, hash: u64, entry: V) -> Option<V> {
+    pub fn remove_entry<P>(&mut self, hash: u64, predicate: P) -> Option<V>
+    where
+        P: FnMut(&V) -> bool,
+    {
         let index = self.bucket(hash);
         let bucket = &mut self.buckets[index];
-        let i = bucket.iter().position(|ek| ek == &entry)?;
+        let i = bucket.iter().position(predicate)?;
         Some(bucket.swap_remove(i))
     }
+    /// Returns the number of entries in all the buckets.
+    #[inline]
+    pub fn items(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.len()).sum()
+    }
+
+    /// Returns the sum of the squared lengths of the buckets, which is the number of entries
+    /// scanned by looking up every entry of the table.
+    #[inline]
+    pub fn probes(&self) -> usize {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.len() * bucket.len())
+            .sum()
+    }
+
+    /// Returns the number of bytes the table owns on the heap: the buckets, and the entries of
+    /// the buckets that spilled out of their inline storage.
+    #[inline]
+    pub fn heap_size(&self) -> usize {
+        let spilled: usize = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.spilled())
+            .map(|bucket| bucket.capacity())
+            .sum();
+        self.buckets.capacity() * mem::size_of::<Bucket<V, N>>() + spilled * mem::size_of::<V>()
+    }
 }
 
-impl<V> Deref for Table<V> {
-    type Target = Vec<Bucket<V>>;
+impl<V, const N: usize> Deref for Table<V, N> {
+    type Target = Vec<Bucket<V, N>>;
     fn deref(&self) -> &Self::Target {
         &self.buckets
     }
 }
 
-impl<V> DerefMut for Table<V> {
+impl<V, const N: usize> DerefMut for Table<V, N> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.buckets
     }
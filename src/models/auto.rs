@@ -0,0 +1,287 @@
+#[cfg(feature = "mmap")]
+use crate::map::{FlatModel, LINEAR_TAG, PIECEWISE_TAG, RMI_TAG, SPLINE_TAG};
+use crate::{
+    error::*,
+    models::{LinearModel, Model, PiecewiseModel, RmiModel, SplineModel},
+};
+
+use core::fmt::Debug;
+use core::iter::Sum;
+use num_traits::{cast::FromPrimitive, float::Float};
+
+/// Every n-th pair of the training data is held out for validation
+const VALIDATION_STEP: usize = 5;
+
+/// Validation score of one candidate model
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelScore<F> {
+    /// Name of the candidate model
+    pub name: String,
+    /// Root mean squared error on the validation data, infinity if the candidate failed to fit
+    pub rmse: F,
+}
+
+/// Model that selects the most accurate of the candidate models on fitting.
+///
+/// The candidates(linear, piecewise, spline and RMI) are fitted on a training split of the data,
+/// the one with the lowest root mean squared error on the validation split is refitted on the
+/// whole data.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{AutoModel, Model};
+/// let xs: Vec<f64> = (0..100).map(|i| (i as f64).powi(3)).collect();
+/// let ys: Vec<f64> = (0..100).map(|i| i as f64).collect();
+/// let (model, scores) = AutoModel::select(&xs, &ys).unwrap();
+///
+/// assert_eq!(4, scores.len());
+/// assert_ne!("linear", model.name());
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AutoModel<F> {
+    Linear(LinearModel<F>),
+    Piecewise(PiecewiseModel<F>),
+    Spline(SplineModel<F>),
+    Rmi(RmiModel<F>),
+}
+
+impl<F> Default for AutoModel<F>
+where
+    F: Float,
+{
+    fn default() -> Self {
+        AutoModel::Linear(LinearModel::new())
+    }
+}
+
+impl<F> AutoModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug,
+{
+    pub fn new() -> AutoModel<F> {
+        Self::default()
+    }
+
+    /// Returns the unfitted candidate models
+    pub fn candidates() -> Vec<AutoModel<F>> {
+        vec![
+            AutoModel::Linear(LinearModel::new()),
+            AutoModel::Piecewise(PiecewiseModel::new()),
+            AutoModel::Spline(SplineModel::new()),
+            AutoModel::Rmi(RmiModel::new()),
+        ]
+    }
+
+    /// Returns the selected model, fitted on the whole data, with the scores of every candidate.
+    ///
+    /// # Arguments
+    /// * `xs` - The training keys
+    /// * `ys` - The training targets
+    pub fn select(xs: &[F], ys: &[F]) -> Result<(AutoModel<F>, Vec<ModelScore<F>>), Error> {
+        assert_empty!(xs);
+        assert_eq_len!(xs, ys);
+        let xys: Vec<(F, F)> = xs.iter().cloned().zip(ys.iter().cloned()).collect();
+        Self::select_tuple(&xys)
+    }
+
+    /// Returns the selected model and the candidate scores from training data in tuple format
+    pub fn select_tuple(xys: &[(F, F)]) -> Result<(AutoModel<F>, Vec<ModelScore<F>>), Error> {
+        assert_empty!(xys);
+
+        // Small data is both trained and validated on the whole data
+        let small = xys.len() < VALIDATION_STEP * 2;
+        let is_validation = |i: usize| small || i % VALIDATION_STEP == VALIDATION_STEP - 1;
+        let train: Vec<(F, F)> = xys
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| small || !is_validation(i))
+            .map(|(_, &xy)| xy)
+            .collect();
+        let (x_test, y_test): (Vec<F>, Vec<F>) = xys
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| is_validation(i))
+            .map(|(_, &xy)| xy)
+            .unzip();
+
+        let mut best: Option<(AutoModel<F>, F)> = None;
+        let mut error = Error::EmptyVal;
+        let mut scores: Vec<ModelScore<F>> = Vec::new();
+        for mut candidate in Self::candidates() {
+            let rmse = match candidate.inner_mut().fit_tuple(&train) {
+                Ok(()) => candidate.evaluate(&x_test, &y_test),
+                Err(err) => {
+                    error = err;
+                    F::infinity()
+                }
+            };
+            scores.push(ModelScore {
+                name: candidate.name(),
+                rmse,
+            });
+            let is_better = match best {
+                Some((_, best_rmse)) => rmse < best_rmse,
+                None => rmse.is_finite(),
+            };
+            if is_better {
+                best = Some((candidate, rmse));
+            }
+        }
+
+        let (mut model, _) = best.ok_or(error)?;
+        model.inner_mut().fit_tuple(xys)?;
+        Ok((model, scores))
+    }
+
+    /// Returns the selected model as a Model trait object
+    #[inline]
+    fn inner(&self) -> &dyn Model<F = F> {
+        match self {
+            AutoModel::Linear(model) => model,
+            AutoModel::Piecewise(model) => model,
+            AutoModel::Spline(model) => model,
+            AutoModel::Rmi(model) => model,
+        }
+    }
+
+    /// Returns the selected model as a mutable Model trait object
+    #[inline]
+    fn inner_mut(&mut self) -> &mut dyn Model<F = F> {
+        match self {
+            AutoModel::Linear(model) => model,
+            AutoModel::Piecewise(model) => model,
+            AutoModel::Spline(model) => model,
+            AutoModel::Rmi(model) => model,
+        }
+    }
+}
+
+impl<F> Model for AutoModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    type F = F;
+
+    fn name(&self) -> String {
+        self.inner().name()
+    }
+
+    /// Selects the most accurate candidate model, and fits it with the data
+    fn fit(&mut self, xs: &[F], ys: &[F]) -> Result<(), Error> {
+        *self = Self::select(xs, ys)?.0;
+        Ok(())
+    }
+
+    /// Selects the most accurate candidate model, and fits it with the data in tuple format
+    fn fit_tuple(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
+        *self = Self::select_tuple(xys)?.0;
+        Ok(())
+    }
+
+    fn predict(&self, x: F) -> F {
+        self.inner().predict(x)
+    }
+
+    fn batch_predict(&self, xs: &[F]) -> Vec<F> {
+        self.inner().batch_predict(xs)
+    }
+
+    fn evaluate(&self, x_test: &[F], y_test: &[F]) -> F {
+        self.inner().evaluate(x_test, y_test)
+    }
+
+    fn unpredict(&self, y: F) -> F {
+        self.inner().unpredict(y)
+    }
+
+    fn heap_size(&self) -> usize {
+        self.inner().heap_size()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F> FlatModel for AutoModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    fn tag(&self) -> u32 {
+        match self {
+            AutoModel::Linear(model) => model.tag(),
+            AutoModel::Piecewise(model) => model.tag(),
+            AutoModel::Spline(model) => model.tag(),
+            AutoModel::Rmi(model) => model.tag(),
+        }
+    }
+
+    fn params(&self) -> Vec<f64> {
+        match self {
+            AutoModel::Linear(model) => model.params(),
+            AutoModel::Piecewise(model) => model.params(),
+            AutoModel::Spline(model) => model.params(),
+            AutoModel::Rmi(model) => model.params(),
+        }
+    }
+
+    /// Returns the model of any tag
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self> {
+        match tag {
+            LINEAR_TAG => LinearModel::from_params(tag, params).map(AutoModel::Linear),
+            PIECEWISE_TAG => PiecewiseModel::from_params(tag, params).map(AutoModel::Piecewise),
+            SPLINE_TAG => SplineModel::from_params(tag, params).map(AutoModel::Spline),
+            RMI_TAG => RmiModel::from_params(tag, params).map(AutoModel::Rmi),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_linear_for_uniform_data() {
+        let xs: Vec<f64> = (0..100).map(|i| i as f64 * 2.).collect();
+        let ys: Vec<f64> = (0..100).map(|i| i as f64).collect();
+        let (model, scores) = AutoModel::select(&xs, &ys).unwrap();
+
+        assert_eq!("linear", model.name());
+        assert_eq!(
+            vec!["linear", "piecewise", "spline", "rmi"],
+            scores.iter().map(|s| s.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_delta!(10., model.predict(20.), 1e-9);
+    }
+
+    #[test]
+    fn select_lowest_rmse() {
+        let xs: Vec<f64> = (0..500).map(|i| (i as f64 / 10.).exp()).collect();
+        let ys: Vec<f64> = (0..500).map(|i| i as f64).collect();
+        let (model, scores) = AutoModel::select(&xs, &ys).unwrap();
+
+        let best = scores
+            .iter()
+            .min_by(|a, b| a.rmse.partial_cmp(&b.rmse).unwrap())
+            .unwrap();
+        assert_eq!(best.name, model.name());
+        assert_ne!("linear", model.name());
+    }
+
+    #[test]
+    fn fit_selects_model() {
+        let xs: Vec<f64> = (0..500).map(|i| (i as f64 / 10.).exp()).collect();
+        let ys: Vec<f64> = (0..500).map(|i| i as f64).collect();
+        let mut model = AutoModel::new();
+        model.fit(&xs, &ys).unwrap();
+
+        assert_ne!("linear", model.name());
+        assert!(model.evaluate(&xs, &ys) < 10.);
+    }
+
+    #[test]
+    fn should_fail_for_empty_vecs() {
+        let mut model = AutoModel::<f64>::new();
+        assert_eq!(Err(Error::EmptyVal), model.fit(&[], &[]));
+    }
+}
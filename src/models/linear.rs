@@ -1,3 +1,5 @@
+#[cfg(feature = "mmap")]
+use crate::map::{param, params_into, FlatModel, LINEAR_TAG};
 use crate::{
     error::*,
     models::{stats::root_mean_squared_error, Model},
@@ -140,6 +142,7 @@ where
 
 /// Linear regression model
 #[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LinearModel<F> {
     pub coefficient: F,
     pub intercept: F,
@@ -168,13 +171,13 @@ where
     }
 
     fn fit(&mut self, xs: &[F], ys: &[F]) -> Result<(), Error> {
-        let (coefficient, intercept): (F, F) = linear_regression(xs, ys).unwrap();
+        let (coefficient, intercept): (F, F) = linear_regression(xs, ys)?;
         self.coefficient = coefficient;
         self.intercept = intercept;
         Ok(())
     }
     fn fit_tuple(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
-        let (coefficient, intercept): (F, F) = linear_regression_tuple(xys).unwrap();
+        let (coefficient, intercept): (F, F) = linear_regression_tuple(xys)?;
         self.coefficient = coefficient;
         self.intercept = intercept;
         Ok(())
@@ -197,6 +200,30 @@ where
     }
 }
 
+#[cfg(feature = "mmap")]
+impl<F> FlatModel for LinearModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    fn tag(&self) -> u32 {
+        LINEAR_TAG
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![param(self.coefficient), param(self.intercept)]
+    }
+
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self> {
+        match (tag, params_into(params)?.as_slice()) {
+            (LINEAR_TAG, &[coefficient, intercept]) => Some(LinearModel {
+                coefficient,
+                intercept,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,6 +240,23 @@ mod tests {
         assert_delta!(0.4, model.intercept, 0.00001);
     }
 
+    #[test]
+    fn should_fail_for_empty_vecs() {
+        let mut model = LinearModel::<f64>::new();
+        assert_eq!(Err(Error::EmptyVal), model.fit(&[], &[]));
+        assert_eq!(Err(Error::EmptyVal), model.fit_tuple(&[]));
+    }
+
+    #[test]
+    fn should_fail_for_repeated_keys() {
+        let mut model = LinearModel::<f64>::new();
+        assert_eq!(Err(Error::SteepSlope), model.fit(&[1., 1.], &[0., 1.]));
+        assert_eq!(
+            Err(Error::SteepSlope),
+            model.fit_tuple(&[(1., 0.), (1., 1.)])
+        );
+    }
+
     #[test]
     fn fit_coefficients() {
         let x_values: Vec<f64> = vec![1., 2., 3., 4., 5.];
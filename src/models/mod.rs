@@ -1,8 +1,18 @@
+mod auto;
 mod linear;
+mod piecewise;
+mod rmi;
+mod segment;
+mod spline;
 mod stats;
 mod trainer;
 
+pub use auto::*;
 pub use linear::*;
+pub use piecewise::*;
+pub use rmi::*;
+pub use segment::Segment;
+pub use spline::*;
 pub use stats::*;
 pub use trainer::*;
 
@@ -29,9 +39,25 @@ pub trait Model {
     /// Unpredict provides the ability of reversing the predict operation
     /// For a given target value, return the estimate input value
     fn unpredict(&self, y: Self::F) -> Self::F;
+    /// Returns the number of bytes the model owns on the heap
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// Returns the predictions of the model for a slice of values, computed in parallel. The
+/// predictions are identical to predicting every value in order.
+#[cfg(feature = "rayon")]
+pub fn par_batch_predict<M, F>(model: &M, xs: &[F]) -> Vec<F>
+where
+    F: Float + Send + Sync,
+    M: Model<F = F> + Sync + ?Sized,
+{
+    use rayon::prelude::*;
+    xs.par_iter().map(|&x| model.predict(x)).collect()
 }
 
-impl<F> Debug for (dyn Model<F = F> + 'static)
+impl<F> Debug for dyn Model<F = F> + 'static
 where
     F: Float,
 {
@@ -0,0 +1,520 @@
+#[cfg(feature = "mmap")]
+use crate::map::{param, params_into, FlatModel, PIECEWISE_TAG};
+#[cfg(feature = "mmap")]
+use crate::models::segment::{push_segment_params, segments_from_params};
+use crate::{
+    error::*,
+    models::{
+        segment::{unpredict_segments, Segment},
+        stats::root_mean_squared_error,
+        Model,
+    },
+};
+
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::iter::Sum;
+use num_traits::{cast::FromPrimitive, float::Float};
+
+/// Default maximum error of the PiecewiseModel
+const DEFAULT_EPSILON: f64 = 8.;
+
+/// Point in the convex hulls of the optimal piecewise linear approximation
+#[derive(Copy, Clone, Debug)]
+struct HullPoint<F> {
+    x: F,
+    y: F,
+}
+
+impl<F: Float> HullPoint<F> {
+    /// Returns the slope from other point to this point
+    #[inline]
+    fn slope_from(&self, other: &HullPoint<F>) -> Slope<F> {
+        Slope {
+            dx: self.x - other.x,
+            dy: self.y - other.y,
+        }
+    }
+}
+
+/// Slope represented by its two deltas, so slopes are compared without divisions
+#[derive(Copy, Clone, Debug)]
+struct Slope<F> {
+    dx: F,
+    dy: F,
+}
+
+impl<F: Float> Slope<F> {
+    #[inline]
+    fn lt(&self, other: &Slope<F>) -> bool {
+        self.dy * other.dx < self.dx * other.dy
+    }
+
+    #[inline]
+    fn gt(&self, other: &Slope<F>) -> bool {
+        self.dy * other.dx > self.dx * other.dy
+    }
+
+    #[inline]
+    fn value(&self) -> F {
+        self.dy / self.dx
+    }
+}
+
+/// Cross product of the vectors OA and OB
+#[inline]
+fn cross<F: Float>(o: &HullPoint<F>, a: &HullPoint<F>, b: &HullPoint<F>) -> F {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+/// Streaming builder of the optimal piecewise linear approximation, it maintains the upper and
+/// lower convex hulls of the points of the current segment, and the rectangle of the extreme
+/// feasible lines (O'Rourke's algorithm, as used by the PGM-index).
+struct SegmentBuilder<F> {
+    epsilon: F,
+    first_x: F,
+    first_y: F,
+    points_in_hull: usize,
+    rectangle: [HullPoint<F>; 4],
+    upper: Vec<HullPoint<F>>,
+    lower: Vec<HullPoint<F>>,
+    upper_start: usize,
+    lower_start: usize,
+}
+
+impl<F: Float> SegmentBuilder<F> {
+    fn new(epsilon: F) -> Self {
+        let origin = HullPoint {
+            x: F::zero(),
+            y: F::zero(),
+        };
+        Self {
+            epsilon,
+            first_x: F::zero(),
+            first_y: F::zero(),
+            points_in_hull: 0,
+            rectangle: [origin; 4],
+            upper: Vec::new(),
+            lower: Vec::new(),
+            upper_start: 0,
+            lower_start: 0,
+        }
+    }
+
+    /// Adds a point into the current segment, returns false if the point can not be covered by
+    /// the current segment within the maximum error.
+    fn add_point(&mut self, x: F, y: F) -> bool {
+        let p1 = HullPoint {
+            x,
+            y: y + self.epsilon,
+        };
+        let p2 = HullPoint {
+            x,
+            y: y - self.epsilon,
+        };
+
+        if self.points_in_hull == 0 {
+            self.first_x = x;
+            self.first_y = y;
+            self.rectangle[0] = p1;
+            self.rectangle[1] = p2;
+            self.upper.clear();
+            self.lower.clear();
+            self.upper.push(p1);
+            self.lower.push(p2);
+            self.upper_start = 0;
+            self.lower_start = 0;
+            self.points_in_hull += 1;
+            return true;
+        }
+
+        if self.points_in_hull == 1 {
+            self.rectangle[2] = p2;
+            self.rectangle[3] = p1;
+            self.upper.push(p1);
+            self.lower.push(p2);
+            self.points_in_hull += 1;
+            return true;
+        }
+
+        let slope1 = self.rectangle[2].slope_from(&self.rectangle[0]);
+        let slope2 = self.rectangle[3].slope_from(&self.rectangle[1]);
+        let outside_line1 = p1.slope_from(&self.rectangle[2]).lt(&slope1);
+        let outside_line2 = p2.slope_from(&self.rectangle[3]).gt(&slope2);
+
+        if outside_line1 || outside_line2 {
+            self.points_in_hull = 0;
+            return false;
+        }
+
+        if p1.slope_from(&self.rectangle[1]).lt(&slope2) {
+            // Find extreme slope
+            let mut min = self.lower[self.lower_start].slope_from(&p1);
+            let mut min_i = self.lower_start;
+            for i in self.lower_start + 1..self.lower.len() {
+                let val = self.lower[i].slope_from(&p1);
+                if val.gt(&min) {
+                    break;
+                }
+                min = val;
+                min_i = i;
+            }
+            self.rectangle[1] = self.lower[min_i];
+            self.rectangle[3] = p1;
+            self.lower_start = min_i;
+
+            // Hull update
+            let mut end = self.upper.len();
+            while end >= self.upper_start + 2
+                && cross(&self.upper[end - 2], &self.upper[end - 1], &p1) <= F::zero()
+            {
+                end -= 1;
+            }
+            self.upper.truncate(end);
+            self.upper.push(p1);
+        }
+
+        if p2.slope_from(&self.rectangle[0]).gt(&slope1) {
+            // Find extreme slope
+            let mut max = self.upper[self.upper_start].slope_from(&p2);
+            let mut max_i = self.upper_start;
+            for i in self.upper_start + 1..self.upper.len() {
+                let val = self.upper[i].slope_from(&p2);
+                if val.lt(&max) {
+                    break;
+                }
+                max = val;
+                max_i = i;
+            }
+            self.rectangle[0] = self.upper[max_i];
+            self.rectangle[2] = p2;
+            self.upper_start = max_i;
+
+            // Hull update
+            let mut end = self.lower.len();
+            while end >= self.lower_start + 2
+                && cross(&self.lower[end - 2], &self.lower[end - 1], &p2) >= F::zero()
+            {
+                end -= 1;
+            }
+            self.lower.truncate(end);
+            self.lower.push(p2);
+        }
+
+        self.points_in_hull += 1;
+        true
+    }
+
+    /// Returns the segment with the slope in the middle of the feasible slopes, passing through
+    /// the intersection of the two diagonals of the rectangle.
+    fn segment(&self) -> Segment<F> {
+        let two = F::one() + F::one();
+        if self.points_in_hull == 1 {
+            return Segment {
+                key: self.first_x,
+                slope: F::zero(),
+                intercept: (self.rectangle[0].y + self.rectangle[1].y) / two,
+                lower: self.first_y,
+                upper: F::infinity(),
+            };
+        }
+
+        let r = &self.rectangle;
+        let slope1 = r[2].slope_from(&r[0]);
+        let slope2 = r[3].slope_from(&r[1]);
+        let a = slope1.dx * slope2.dy - slope1.dy * slope2.dx;
+        let (i_x, i_y) = if a == F::zero() {
+            (r[0].x, (r[0].y + r[1].y) / two)
+        } else {
+            let b =
+                ((r[1].x - r[0].x) * (r[3].y - r[1].y) - (r[1].y - r[0].y) * (r[3].x - r[1].x)) / a;
+            (r[0].x + b * slope1.dx, r[0].y + b * slope1.dy)
+        };
+        let slope = ((slope1.value() + slope2.value()) / two).max(F::zero());
+        Segment {
+            key: self.first_x,
+            slope,
+            intercept: i_y - (i_x - self.first_x) * slope,
+            lower: self.first_y,
+            upper: F::infinity(),
+        }
+    }
+}
+
+/// Piecewise linear model, an optimal piecewise linear approximation of the CDF of the training
+/// data, where the prediction of every training key is guaranteed to be within `epsilon` of its
+/// target.
+///
+/// Keys are expected to have increasing targets(e.g. ranks from the Trainer). Repeated keys are
+/// fitted with the target of their first occurrence.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{Model, PiecewiseModel};
+/// let xs: Vec<f64> = vec![1., 2., 3., 10., 11., 12.];
+/// let ys: Vec<f64> = vec![0., 1., 2., 3., 4., 5.];
+/// let mut model = PiecewiseModel::with_epsilon(0.5);
+/// model.fit(&xs, &ys).unwrap();
+///
+/// assert!((model.predict(10.) - 3.).abs() <= 0.5);
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PiecewiseModel<F> {
+    epsilon: F,
+    segments: Vec<Segment<F>>,
+}
+
+impl<F> Default for PiecewiseModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    fn default() -> Self {
+        Self::with_epsilon(F::from_f64(DEFAULT_EPSILON).unwrap())
+    }
+}
+
+impl<F> PiecewiseModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    pub fn new() -> PiecewiseModel<F> {
+        Self::default()
+    }
+
+    /// Returns an empty PiecewiseModel with the maximum error.
+    ///
+    /// # Arguments
+    /// * `epsilon` - The maximum error between the prediction and target of a training key
+    pub fn with_epsilon(epsilon: F) -> PiecewiseModel<F> {
+        PiecewiseModel {
+            epsilon: epsilon.abs(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Returns the maximum error of the model
+    pub fn epsilon(&self) -> F {
+        self.epsilon
+    }
+
+    /// Returns the fitted segments of the model
+    pub fn segments(&self) -> &[Segment<F>] {
+        &self.segments
+    }
+
+    /// Builds the segments from pairs of training data sorted by key
+    fn build(&mut self, xys: &[(F, F)]) {
+        let mut builder = SegmentBuilder::new(self.epsilon);
+        let mut segments: Vec<Segment<F>> = Vec::new();
+        let mut last_x: Option<F> = None;
+        for &(x, y) in xys.iter() {
+            // Repeated keys are covered by their first occurrence
+            if last_x == Some(x) {
+                continue;
+            }
+            last_x = Some(x);
+            if !builder.add_point(x, y) {
+                segments.push(builder.segment());
+                builder.add_point(x, y);
+            }
+        }
+        segments.push(builder.segment());
+
+        // Link the bounds of neighbor segments
+        for i in 1..segments.len() {
+            segments[i - 1].upper = segments[i].lower;
+        }
+        segments[0].lower = F::neg_infinity();
+        self.segments = segments;
+    }
+
+    /// Returns the index of the segment covering given key
+    #[inline]
+    fn segment_index(&self, x: F) -> usize {
+        self.segments
+            .partition_point(|s| s.key <= x)
+            .saturating_sub(1)
+    }
+}
+
+impl<F> Model for PiecewiseModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    type F = F;
+
+    fn name(&self) -> String {
+        String::from("piecewise")
+    }
+
+    fn fit(&mut self, xs: &[F], ys: &[F]) -> Result<(), Error> {
+        assert_empty!(xs);
+        assert_eq_len!(xs, ys);
+        let xys: Vec<(F, F)> = xs.iter().cloned().zip(ys.iter().cloned()).collect();
+        self.fit_tuple(&xys)
+    }
+
+    fn fit_tuple(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
+        assert_empty!(xys);
+        assert_finite!(xys);
+        if xys.windows(2).all(|w| w[0] <= w[1]) {
+            self.build(xys);
+        } else {
+            let mut sorted = xys.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            self.build(&sorted);
+        }
+        Ok(())
+    }
+
+    fn predict(&self, x: F) -> F {
+        if self.segments.is_empty() {
+            return F::zero();
+        }
+        self.segments[self.segment_index(x)].predict(x)
+    }
+
+    fn batch_predict(&self, xs: &[F]) -> Vec<F> {
+        (0..xs.len()).map(|i| self.predict(xs[i])).collect()
+    }
+
+    fn evaluate(&self, x_test: &[F], y_test: &[F]) -> F {
+        let y_predicted = self.batch_predict(x_test);
+        root_mean_squared_error(y_test, &y_predicted)
+    }
+
+    /// Returns the smallest key with prediction larger or equal to the given target
+    fn unpredict(&self, y: F) -> F {
+        unpredict_segments(&self.segments, y)
+    }
+
+    fn heap_size(&self) -> usize {
+        self.segments.capacity() * core::mem::size_of::<Segment<F>>()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F> FlatModel for PiecewiseModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    fn tag(&self) -> u32 {
+        PIECEWISE_TAG
+    }
+
+    fn params(&self) -> Vec<f64> {
+        let mut params = vec![param(self.epsilon)];
+        push_segment_params(&self.segments, &mut params);
+        params
+    }
+
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self> {
+        if tag != PIECEWISE_TAG {
+            return None;
+        }
+        let params: Vec<F> = params_into(params)?;
+        let (&epsilon, segments) = params.split_first()?;
+        Some(PiecewiseModel {
+            epsilon,
+            segments: segments_from_params(segments)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::clustered_data;
+
+    #[test]
+    fn fit_within_epsilon() {
+        let (xs, ys) = clustered_data();
+        for epsilon in [0.5, 2., 8., 64.] {
+            let mut model = PiecewiseModel::with_epsilon(epsilon);
+            model.fit(&xs, &ys).unwrap();
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                assert!((model.predict(*x) - y).abs() <= epsilon + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn fewer_segments_with_larger_epsilon() {
+        let (xs, ys) = clustered_data();
+        let mut small = PiecewiseModel::with_epsilon(1.);
+        let mut large = PiecewiseModel::with_epsilon(32.);
+        small.fit(&xs, &ys).unwrap();
+        large.fit(&xs, &ys).unwrap();
+        assert!(large.segments().len() < small.segments().len());
+        // Three linear clusters and a quadratic one fit in a handful of segments
+        assert!(large.segments().len() < 10);
+    }
+
+    #[test]
+    fn monotonic_predict() {
+        let (xs, ys) = clustered_data();
+        let mut model = PiecewiseModel::with_epsilon(4.);
+        model.fit(&xs, &ys).unwrap();
+        // Segments start at increasing keys and never predict below the end of the previous one
+        for w in model.segments().windows(2) {
+            assert!(w[0].key < w[1].key);
+            assert!(w[0].predict(w[1].key) <= w[1].predict(w[1].key));
+        }
+        let mut last = f64::NEG_INFINITY;
+        let mut x = -10.;
+        while x < 7000. {
+            let y = model.predict(x);
+            assert!(y >= last);
+            last = y;
+            x += 0.37;
+        }
+    }
+
+    #[test]
+    fn unpredict() {
+        let (xs, ys) = clustered_data();
+        let mut model = PiecewiseModel::with_epsilon(4.);
+        model.fit(&xs, &ys).unwrap();
+        for y in ys.iter() {
+            let x = model.unpredict(*y);
+            for key in xs.iter() {
+                if *key < x - 1e-9 {
+                    assert!(model.predict(*key) < *y);
+                } else if *key >= x {
+                    assert!(model.predict(*key) >= *y - 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let xs: Vec<f64> = vec![1., 1., 1., 2., 3., 3., 4.];
+        let ys: Vec<f64> = (0..xs.len()).map(|i| i as f64).collect();
+        let mut model = PiecewiseModel::with_epsilon(1.);
+        model.fit(&xs, &ys).unwrap();
+        assert!((model.predict(2.) - 3.).abs() <= 1.);
+        assert!((model.predict(4.) - 6.).abs() <= 1.);
+    }
+
+    #[test]
+    fn should_fail_for_empty_vecs() {
+        let mut model = PiecewiseModel::<f64>::new();
+        assert_eq!(Err(Error::EmptyVal), model.fit(&[], &[]));
+    }
+
+    #[test]
+    fn should_fail_for_non_finite_keys() {
+        let mut model = PiecewiseModel::<f64>::new();
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            model.fit(&[1., f64::NAN, 2.], &[0., 1., 2.])
+        );
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            model.fit_tuple(&[(1., 0.), (f64::INFINITY, 1.)])
+        );
+    }
+}
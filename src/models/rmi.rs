@@ -0,0 +1,422 @@
+#[cfg(feature = "mmap")]
+use crate::map::{param, param_count, params_into, FlatModel, LINEAR_TAG, RMI_TAG};
+#[cfg(feature = "mmap")]
+use crate::models::segment::{push_segment_params, segments_from_params};
+use crate::{
+    error::*,
+    models::{
+        segment::{unpredict_segments, Segment},
+        stats::root_mean_squared_error,
+        LinearModel, Model,
+    },
+};
+
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::iter::Sum;
+use num_traits::{cast::FromPrimitive, float::Float};
+
+/// Default number of leaf models of the RmiModel
+const DEFAULT_LEAVES: usize = 32;
+
+/// Two-stage Recursive Model Index(RMI) model.
+///
+/// A root LinearModel routes each key to one of the leaf models, and each leaf model is a linear
+/// model fitted on its own slice of the sorted training data. The predictions of the leaves are
+/// clamped between the targets of their first keys, so the model stays monotonic across leaf
+/// boundaries.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{Model, RmiModel};
+/// let xs: Vec<f64> = vec![1., 2., 3., 10., 11., 12.];
+/// let ys: Vec<f64> = vec![0., 1., 2., 3., 4., 5.];
+/// let mut model = RmiModel::with_leaves(4);
+/// model.fit(&xs, &ys).unwrap();
+///
+/// assert_eq!(model.n_leaves(), 4);
+/// assert!(model.predict(3.) <= model.predict(10.));
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RmiModel<F> {
+    n_leaves: usize,
+    size: F,
+    root: LinearModel<F>,
+    leaves: Vec<Segment<F>>,
+}
+
+impl<F> Default for RmiModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    fn default() -> Self {
+        Self::with_leaves(DEFAULT_LEAVES)
+    }
+}
+
+impl<F> RmiModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    pub fn new() -> RmiModel<F> {
+        Self::default()
+    }
+
+    /// Returns an empty RmiModel with the number of leaf models.
+    ///
+    /// # Arguments
+    /// * `n_leaves` - The number of second stage models, at least one
+    pub fn with_leaves(n_leaves: usize) -> RmiModel<F> {
+        RmiModel {
+            n_leaves: n_leaves.max(1),
+            size: F::zero(),
+            root: LinearModel::new(),
+            leaves: Vec::new(),
+        }
+    }
+
+    /// Returns the number of leaf models
+    pub fn n_leaves(&self) -> usize {
+        self.n_leaves
+    }
+
+    /// Returns the root model
+    pub fn root(&self) -> &LinearModel<F> {
+        &self.root
+    }
+
+    /// Returns the fitted leaf models, as segments starting at the smallest key routed to them
+    pub fn leaves(&self) -> &[Segment<F>] {
+        &self.leaves
+    }
+}
+
+impl<F> RmiModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug,
+{
+    /// Returns the index of the leaf model that the root model routes the key to
+    #[inline]
+    fn leaf_index(&self, x: F) -> usize {
+        let n = F::from_usize(self.n_leaves).unwrap();
+        let i = (self.root.predict(x) * n / self.size).floor();
+        if i.is_nan() || i < F::zero() {
+            return 0;
+        }
+        i.to_usize().unwrap_or(usize::MAX).min(self.n_leaves - 1)
+    }
+
+    /// Returns the smallest key that the root model routes to the leaf model
+    #[inline]
+    fn leaf_key(&self, i: usize) -> F {
+        let n = F::from_usize(self.n_leaves).unwrap();
+        self.root
+            .unpredict(F::from_usize(i).unwrap() * self.size / n)
+    }
+
+    /// Fits a leaf model with its slice of the training data, returns None for an empty slice
+    fn fit_leaf(key: F, xys: &[(F, F)]) -> Result<Option<Segment<F>>, Error> {
+        let (first_x, first_y) = match xys.first() {
+            Some(&xy) => xy,
+            None => return Ok(None),
+        };
+        let mut segment = Segment {
+            key,
+            slope: F::zero(),
+            intercept: first_y,
+            lower: first_y,
+            upper: F::infinity(),
+        };
+        if xys.iter().any(|&(x, _)| x != first_x) {
+            let mut model = LinearModel::new();
+            model.fit_tuple(xys)?;
+            segment.slope = model.coefficient.max(F::zero());
+            segment.intercept = model.predict(key);
+        }
+        Ok(Some(segment))
+    }
+
+    /// Builds the root and leaf models from pairs of training data sorted by key
+    fn build(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
+        self.size = F::from_usize(xys.len()).unwrap();
+        self.root.fit_tuple(xys)?;
+
+        let mut leaves: Vec<Option<Segment<F>>> = Vec::with_capacity(self.n_leaves);
+        let mut keys: Vec<F> = Vec::with_capacity(self.n_leaves);
+        let mut start = 0;
+        for i in 0..self.n_leaves {
+            let end = start + xys[start..].partition_point(|&(x, _)| self.leaf_index(x) <= i);
+            let key = match i {
+                0 if self.n_leaves > 1 => xys[0].0.min(self.leaf_key(1)),
+                0 => xys[0].0,
+                _ => self.leaf_key(i),
+            };
+            leaves.push(Self::fit_leaf(key, &xys[start..end])?);
+            keys.push(key);
+            start = end;
+        }
+
+        // Link the bounds of neighbor leaves, empty leaves predict the target of the next key
+        let mut next_lower = xys[xys.len() - 1].1;
+        let mut segments: Vec<Segment<F>> = Vec::with_capacity(self.n_leaves);
+        for (leaf, key) in leaves.into_iter().zip(keys).rev() {
+            let segment = match leaf {
+                Some(mut segment) => {
+                    if !segments.is_empty() {
+                        segment.upper = next_lower;
+                    }
+                    segment
+                }
+                None => Segment {
+                    key,
+                    slope: F::zero(),
+                    intercept: next_lower,
+                    lower: next_lower,
+                    upper: next_lower,
+                },
+            };
+            next_lower = segment.lower;
+            segments.push(segment);
+        }
+        segments.reverse();
+        if segments[0].upper > segments[0].lower {
+            segments[0].lower = F::neg_infinity();
+        }
+        self.leaves = segments;
+        Ok(())
+    }
+}
+
+impl<F> Model for RmiModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    type F = F;
+
+    fn name(&self) -> String {
+        String::from("rmi")
+    }
+
+    fn fit(&mut self, xs: &[F], ys: &[F]) -> Result<(), Error> {
+        assert_empty!(xs);
+        assert_eq_len!(xs, ys);
+        let xys: Vec<(F, F)> = xs.iter().cloned().zip(ys.iter().cloned()).collect();
+        self.fit_tuple(&xys)
+    }
+
+    fn fit_tuple(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
+        assert_empty!(xys);
+        assert_finite!(xys);
+        if xys.windows(2).all(|w| w[0] <= w[1]) {
+            self.build(xys)
+        } else {
+            let mut sorted = xys.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            self.build(&sorted)
+        }
+    }
+
+    fn predict(&self, x: F) -> F {
+        if self.leaves.is_empty() {
+            return F::zero();
+        }
+        self.leaves[self.leaf_index(x)].predict(x)
+    }
+
+    fn batch_predict(&self, xs: &[F]) -> Vec<F> {
+        (0..xs.len()).map(|i| self.predict(xs[i])).collect()
+    }
+
+    fn evaluate(&self, x_test: &[F], y_test: &[F]) -> F {
+        let y_predicted = self.batch_predict(x_test);
+        root_mean_squared_error(y_test, &y_predicted)
+    }
+
+    /// Returns the smallest key with prediction larger or equal to the given target
+    fn unpredict(&self, y: F) -> F {
+        unpredict_segments(&self.leaves, y)
+    }
+
+    fn heap_size(&self) -> usize {
+        self.leaves.capacity() * core::mem::size_of::<Segment<F>>()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F> FlatModel for RmiModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    fn tag(&self) -> u32 {
+        RMI_TAG
+    }
+
+    fn params(&self) -> Vec<f64> {
+        let mut params = vec![self.n_leaves as f64, param(self.size)];
+        params.extend(self.root.params());
+        push_segment_params(&self.leaves, &mut params);
+        params
+    }
+
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self> {
+        if tag != RMI_TAG || params.len() < 4 {
+            return None;
+        }
+        let n_leaves = param_count(params[0]).filter(|&n| n > 0)?;
+        let root = LinearModel::from_params(LINEAR_TAG, &params[2..4])?;
+        let params: Vec<F> = params_into(params)?;
+        // The root routes to every leaf, an unfitted model has none
+        let leaves = segments_from_params(&params[4..])?;
+        if !leaves.is_empty() && leaves.len() != n_leaves {
+            return None;
+        }
+        Some(RmiModel {
+            n_leaves,
+            size: params[1],
+            root,
+            leaves,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::clustered_data;
+
+    #[test]
+    fn route_keys_to_leaves() {
+        let xs: Vec<f64> = (0..100).map(|i| i as f64).collect();
+        let ys: Vec<f64> = xs.clone();
+        let mut model = RmiModel::with_leaves(4);
+        model.fit(&xs, &ys).unwrap();
+
+        // The root predicts the rank, each leaf covers a quarter of the ranks
+        assert_delta!(1f64, model.root().coefficient, 0.00001);
+        assert_delta!(0f64, model.root().intercept, 0.00001);
+        assert_eq!(0, model.leaf_index(-5.));
+        assert_eq!(0, model.leaf_index(24.5));
+        assert_eq!(1, model.leaf_index(25.5));
+        assert_eq!(2, model.leaf_index(74.5));
+        assert_eq!(3, model.leaf_index(75.5));
+        assert_eq!(3, model.leaf_index(1000.));
+        for (i, leaf) in model.leaves().iter().enumerate() {
+            assert_delta!(25. * i as f64, leaf.key, 0.00001);
+            assert_delta!(1f64, leaf.slope, 0.00001);
+        }
+        assert_delta!(30f64, model.predict(30.), 0.00001);
+        assert_delta!(99f64, model.predict(99.), 0.00001);
+    }
+
+    #[test]
+    fn link_leaf_bounds() {
+        let (xs, ys) = clustered_data();
+        let mut model = RmiModel::with_leaves(16);
+        model.fit(&xs, &ys).unwrap();
+        let leaves = model.leaves();
+        assert_eq!(16, leaves.len());
+        assert!(leaves[15].upper >= ys[ys.len() - 1]);
+        for w in leaves.windows(2) {
+            assert!(w[0].key <= w[1].key);
+            assert_eq!(w[0].upper, w[1].lower);
+        }
+        // Keys are predicted within the bounds of the leaf they are routed to
+        for x in xs.iter() {
+            let leaf = &leaves[model.leaf_index(*x)];
+            let y = model.predict(*x);
+            assert!(leaf.lower <= y && y <= leaf.upper);
+        }
+    }
+
+    #[test]
+    fn empty_leaves_predict_next_target() {
+        let xs: Vec<f64> = vec![0., 1., 2., 100.];
+        let ys: Vec<f64> = vec![0., 1., 2., 3.];
+        let mut model = RmiModel::with_leaves(8);
+        model.fit(&xs, &ys).unwrap();
+        let empty = (0..8)
+            .filter(|&i| xs.iter().all(|x| model.leaf_index(*x) != i))
+            .collect::<Vec<_>>();
+        assert!(!empty.is_empty());
+        for i in empty {
+            let leaf = &model.leaves()[i];
+            assert_eq!(0., leaf.slope);
+            assert_eq!(leaf.lower, leaf.upper);
+        }
+        assert_delta!(3f64, model.predict(100.), 0.00001);
+    }
+
+    #[test]
+    fn more_accurate_than_linear() {
+        let (xs, ys) = clustered_data();
+        let mut linear = LinearModel::new();
+        linear.fit(&xs, &ys).unwrap();
+        let mut rmi = RmiModel::with_leaves(64);
+        rmi.fit(&xs, &ys).unwrap();
+        assert_eq!(64, rmi.leaves().len());
+        assert!(rmi.evaluate(&xs, &ys) < linear.evaluate(&xs, &ys));
+    }
+
+    #[test]
+    fn monotonic_predict() {
+        let (xs, ys) = clustered_data();
+        for n_leaves in [1, 7, 100] {
+            let mut model = RmiModel::with_leaves(n_leaves);
+            model.fit(&xs, &ys).unwrap();
+            let mut last = f64::NEG_INFINITY;
+            let mut x = -10.;
+            while x < 7000. {
+                let y = model.predict(x);
+                assert!(y >= last);
+                last = y;
+                x += 0.37;
+            }
+        }
+    }
+
+    #[test]
+    fn unpredict() {
+        let (xs, ys) = clustered_data();
+        let mut model = RmiModel::with_leaves(16);
+        model.fit(&xs, &ys).unwrap();
+        for y in ys.iter() {
+            let x = model.unpredict(*y);
+            for key in xs.iter() {
+                if *key < x - 1e-9 {
+                    assert!(model.predict(*key) < *y);
+                } else if *key >= x {
+                    assert!(model.predict(*key) >= *y - 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_leaf_is_linear() {
+        let xs: Vec<f64> = vec![1., 2., 3., 4., 5.];
+        let ys: Vec<f64> = vec![1., 3., 2., 3., 5.];
+        let mut model = RmiModel::with_leaves(1);
+        model.fit(&xs, &ys).unwrap();
+        assert_delta!(1.2f64, model.predict(1f64), 0.00001);
+        assert_delta!(2.8f64, model.predict(3f64), 0.00001);
+        assert_delta!(4.4f64, model.predict(5f64), 0.00001);
+    }
+
+    #[test]
+    fn should_fail_for_empty_vecs() {
+        let mut model = RmiModel::<f64>::new();
+        assert_eq!(Err(Error::EmptyVal), model.fit(&[], &[]));
+    }
+
+    #[test]
+    fn should_fail_for_non_finite_keys() {
+        let mut model = RmiModel::<f64>::new();
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            model.fit(&[1., f64::NEG_INFINITY, 2.], &[0., 1., 2.])
+        );
+        assert!(model.leaves().is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use num_traits::float::Float;
+
+/// Number of parameters of a segment in the flat file format
+#[cfg(feature = "mmap")]
+const SEGMENT_PARAMS: usize = 5;
+
+/// Segment of a piecewise model, covers the keys from `key` up to the key of the next segment.
+///
+/// The prediction of the segment is clamped into `[lower, upper]`, which are the targets of
+/// the first key of this segment and of the next segment, so the model stays monotonic across
+/// segment boundaries.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment<F> {
+    pub key: F,
+    pub slope: F,
+    pub intercept: F,
+    pub lower: F,
+    pub upper: F,
+}
+
+impl<F: Float> Segment<F> {
+    /// Returns the prediction of the segment for given value
+    #[inline]
+    pub fn predict(&self, x: F) -> F {
+        (self.intercept + self.slope * (x - self.key))
+            .max(self.lower)
+            .min(self.upper)
+    }
+}
+
+/// Returns the smallest key with prediction larger or equal to the given target, over a list of
+/// monotonic segments sorted by key.
+///
+/// # Arguments
+/// * `segments` - A slice of segments sorted by key
+/// * `y` - A target value
+pub(crate) fn unpredict_segments<F: Float>(segments: &[Segment<F>], y: F) -> F {
+    if segments.is_empty() {
+        return F::zero();
+    }
+    // Segments after the i-th one all start at or above the target
+    let i = segments.partition_point(|s| s.predict(s.key) < y);
+    if i == 0 {
+        let first = &segments[0];
+        if first.slope > F::zero() {
+            return (first.key + (y - first.intercept) / first.slope).min(first.key);
+        }
+        // Keys before the first segment are all predicted to the start of the segment
+        return F::neg_infinity();
+    }
+    let segment = &segments[i - 1];
+    let next_key = match segments.get(i) {
+        Some(next) => next.key,
+        None => F::infinity(),
+    };
+    if y > segment.upper || segment.slope <= F::zero() {
+        // The target falls between the predictions of two neighbor segments
+        next_key
+    } else {
+        (segment.key + (y - segment.intercept) / segment.slope)
+            .max(segment.key)
+            .min(next_key)
+    }
+}
+
+/// Appends the parameters of the segments in the flat file format
+#[cfg(feature = "mmap")]
+pub(crate) fn push_segment_params<F: Float>(segments: &[Segment<F>], params: &mut Vec<f64>) {
+    use crate::map::param;
+    for s in segments.iter() {
+        params.extend([s.key, s.slope, s.intercept, s.lower, s.upper].map(param));
+    }
+}
+
+/// Returns the segments of the parameters in the flat file format, or None if the number of
+/// parameters does not match
+#[cfg(feature = "mmap")]
+pub(crate) fn segments_from_params<F: Float>(params: &[F]) -> Option<Vec<Segment<F>>> {
+    if !params.len().is_multiple_of(SEGMENT_PARAMS) {
+        return None;
+    }
+    let segments = params
+        .chunks_exact(SEGMENT_PARAMS)
+        .map(|s| Segment {
+            key: s[0],
+            slope: s[1],
+            intercept: s[2],
+            lower: s[3],
+            upper: s[4],
+        })
+        .collect();
+    Some(segments)
+}
@@ -0,0 +1,486 @@
+#[cfg(feature = "mmap")]
+use crate::map::{param, param_count, params_into, FlatModel, SPLINE_TAG};
+use crate::{
+    error::*,
+    models::{stats::root_mean_squared_error, Model},
+};
+
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::iter::Sum;
+use num_traits::{cast::FromPrimitive, float::Float};
+
+/// Default number of knots of the SplineModel
+const DEFAULT_KNOTS: usize = 32;
+
+/// Monotone cubic spline model.
+///
+/// The spline interpolates the targets of knots chosen evenly from the sorted training keys, with
+/// tangents limited by the Fritsch–Carlson method so the model is monotonic whenever the targets
+/// are. Keys outside of the knots are extrapolated linearly with the tangents of the end knots.
+///
+/// Keys are expected to have increasing targets(e.g. ranks from the Trainer). Repeated keys are
+/// fitted with the target of their first occurrence.
+///
+/// # Examples
+///
+/// ```
+/// use lsph::{Model, SplineModel};
+/// let xs: Vec<f64> = vec![1., 2., 3., 10., 11., 12.];
+/// let ys: Vec<f64> = vec![0., 1., 2., 3., 4., 5.];
+/// let mut model = SplineModel::with_knots(6);
+/// model.fit(&xs, &ys).unwrap();
+///
+/// assert!((model.predict(10.) - 3.).abs() < 1e-9);
+/// assert!(model.predict(5.) <= model.predict(6.));
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplineModel<F> {
+    n_knots: usize,
+    xs: Vec<F>,
+    ys: Vec<F>,
+    tangents: Vec<F>,
+}
+
+impl<F> Default for SplineModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    fn default() -> Self {
+        Self::with_knots(DEFAULT_KNOTS)
+    }
+}
+
+impl<F> SplineModel<F>
+where
+    F: Float + FromPrimitive,
+{
+    pub fn new() -> SplineModel<F> {
+        Self::default()
+    }
+
+    /// Returns an empty SplineModel with the maximum number of knots.
+    ///
+    /// # Arguments
+    /// * `n_knots` - The maximum number of knots, at least two
+    pub fn with_knots(n_knots: usize) -> SplineModel<F> {
+        SplineModel {
+            n_knots: n_knots.max(2),
+            xs: Vec::new(),
+            ys: Vec::new(),
+            tangents: Vec::new(),
+        }
+    }
+
+    /// Returns the maximum number of knots
+    pub fn n_knots(&self) -> usize {
+        self.n_knots
+    }
+
+    /// Returns the fitted knots as pairs of key and target
+    pub fn knots(&self) -> Vec<(F, F)> {
+        self.xs
+            .iter()
+            .cloned()
+            .zip(self.ys.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the fitted tangents at the knots
+    pub fn tangents(&self) -> &[F] {
+        &self.tangents
+    }
+
+    /// Builds the knots and tangents from pairs of training data sorted by key
+    fn build(&mut self, xys: &[(F, F)]) {
+        // Unique keys with monotonic targets
+        let mut uniques: Vec<(F, F)> = Vec::with_capacity(xys.len());
+        for &(x, y) in xys.iter() {
+            match uniques.last() {
+                Some(&(last_x, _)) if last_x == x => continue,
+                Some(&(_, last_y)) => uniques.push((x, y.max(last_y))),
+                None => uniques.push((x, y)),
+            }
+        }
+
+        // Knots chosen evenly from the unique keys, including both ends
+        let n = self.n_knots.min(uniques.len());
+        let last = uniques.len() - 1;
+        self.xs.clear();
+        self.ys.clear();
+        for i in 0..n {
+            let index = if n == 1 { 0 } else { i * last / (n - 1) };
+            self.xs.push(uniques[index].0);
+            self.ys.push(uniques[index].1);
+        }
+        self.tangents = fritsch_carlson(&self.xs, &self.ys);
+    }
+
+    /// Returns the prediction of the cubic Hermite segment starting at the knot
+    #[inline]
+    fn predict_segment(&self, k: usize, x: F) -> F {
+        let two = F::from_f64(2.).unwrap();
+        let three = F::from_f64(3.).unwrap();
+        let h = self.xs[k + 1] - self.xs[k];
+        let t = (x - self.xs[k]) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let h00 = two * t3 - three * t2 + F::one();
+        let h10 = t3 - two * t2 + t;
+        let h01 = three * t2 - two * t3;
+        let h11 = t3 - t2;
+        let y = h00 * self.ys[k]
+            + h10 * h * self.tangents[k]
+            + h01 * self.ys[k + 1]
+            + h11 * h * self.tangents[k + 1];
+        // Rounding errors could step out of the bounds of the segment
+        y.max(self.ys[k]).min(self.ys[k + 1])
+    }
+}
+
+/// Returns the tangents at the knots of a monotone cubic spline by the Fritsch–Carlson method
+fn fritsch_carlson<F: Float + FromPrimitive>(xs: &[F], ys: &[F]) -> Vec<F> {
+    let n = xs.len();
+    if n < 2 {
+        return vec![F::zero(); n];
+    }
+    let deltas: Vec<F> = (0..n - 1)
+        .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+        .collect();
+
+    let two = F::from_f64(2.).unwrap();
+    let mut tangents: Vec<F> = Vec::with_capacity(n);
+    tangents.push(deltas[0]);
+    for k in 1..n - 1 {
+        if deltas[k - 1] * deltas[k] <= F::zero() {
+            tangents.push(F::zero());
+        } else {
+            tangents.push((deltas[k - 1] + deltas[k]) / two);
+        }
+    }
+    tangents.push(deltas[n - 2]);
+
+    let nine = F::from_f64(9.).unwrap();
+    let three = F::from_f64(3.).unwrap();
+    for k in 0..n - 1 {
+        if deltas[k] == F::zero() {
+            tangents[k] = F::zero();
+            tangents[k + 1] = F::zero();
+            continue;
+        }
+        let alpha = tangents[k] / deltas[k];
+        let beta = tangents[k + 1] / deltas[k];
+        let norm = alpha * alpha + beta * beta;
+        if norm > nine {
+            let tau = three / norm.sqrt();
+            tangents[k] = tau * alpha * deltas[k];
+            tangents[k + 1] = tau * beta * deltas[k];
+        }
+    }
+    tangents
+}
+
+impl<F> Model for SplineModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    type F = F;
+
+    fn name(&self) -> String {
+        String::from("spline")
+    }
+
+    fn fit(&mut self, xs: &[F], ys: &[F]) -> Result<(), Error> {
+        assert_empty!(xs);
+        assert_eq_len!(xs, ys);
+        let xys: Vec<(F, F)> = xs.iter().cloned().zip(ys.iter().cloned()).collect();
+        self.fit_tuple(&xys)
+    }
+
+    fn fit_tuple(&mut self, xys: &[(F, F)]) -> Result<(), Error> {
+        assert_empty!(xys);
+        assert_finite!(xys);
+        if xys.windows(2).all(|w| w[0] <= w[1]) {
+            self.build(xys);
+        } else {
+            let mut sorted = xys.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            self.build(&sorted);
+        }
+        Ok(())
+    }
+
+    fn predict(&self, x: F) -> F {
+        let n = self.xs.len();
+        if n == 0 {
+            return F::zero();
+        }
+        if n == 1 {
+            return self.ys[0];
+        }
+        if x <= self.xs[0] {
+            return self.ys[0] + self.tangents[0] * (x - self.xs[0]);
+        }
+        if x >= self.xs[n - 1] {
+            return self.ys[n - 1] + self.tangents[n - 1] * (x - self.xs[n - 1]);
+        }
+        let k = self.xs.partition_point(|&key| key <= x) - 1;
+        self.predict_segment(k, x)
+    }
+
+    fn batch_predict(&self, xs: &[F]) -> Vec<F> {
+        (0..xs.len()).map(|i| self.predict(xs[i])).collect()
+    }
+
+    fn evaluate(&self, x_test: &[F], y_test: &[F]) -> F {
+        let y_predicted = self.batch_predict(x_test);
+        root_mean_squared_error(y_test, &y_predicted)
+    }
+
+    /// Returns the smallest key with prediction larger or equal to the given target, the cubic
+    /// segment containing the target is inverted by bisection
+    fn unpredict(&self, y: F) -> F {
+        let n = self.xs.len();
+        if n == 0 {
+            return F::neg_infinity();
+        }
+        if y <= self.ys[0] {
+            if n == 1 || self.tangents[0] <= F::zero() {
+                return F::neg_infinity();
+            }
+            return self.xs[0] + (y - self.ys[0]) / self.tangents[0];
+        }
+        if y > self.ys[n - 1] {
+            if n == 1 || self.tangents[n - 1] <= F::zero() {
+                return F::infinity();
+            }
+            return self.xs[n - 1] + (y - self.ys[n - 1]) / self.tangents[n - 1];
+        }
+
+        // The first knot reaching the target closes the segment containing it
+        let k = self.ys.partition_point(|&target| target < y) - 1;
+        let two = F::from_f64(2.).unwrap();
+        let mut lo = self.xs[k];
+        let mut hi = self.xs[k + 1];
+        loop {
+            let mid = lo + (hi - lo) / two;
+            if mid <= lo || mid >= hi {
+                break;
+            }
+            if self.predict_segment(k, mid) >= y {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        // The lower end never overestimates the key, which keeps distance bounds valid
+        lo
+    }
+
+    fn heap_size(&self) -> usize {
+        (self.xs.capacity() + self.ys.capacity() + self.tangents.capacity())
+            * core::mem::size_of::<F>()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F> FlatModel for SplineModel<F>
+where
+    F: Float + FromPrimitive + Sum + Debug + Sized,
+{
+    fn tag(&self) -> u32 {
+        SPLINE_TAG
+    }
+
+    fn params(&self) -> Vec<f64> {
+        let mut params = vec![self.n_knots as f64];
+        for values in [&self.xs, &self.ys, &self.tangents] {
+            params.extend(values.iter().map(|&v| param(v)));
+        }
+        params
+    }
+
+    fn from_params(tag: u32, params: &[f64]) -> Option<Self> {
+        if tag != SPLINE_TAG {
+            return None;
+        }
+        let (&n_knots, knots) = params.split_first()?;
+        let n_knots = param_count(n_knots).filter(|&n| n >= 2)?;
+        let len = knots.len() / 3;
+        if !knots.len().is_multiple_of(3) || len > n_knots {
+            return None;
+        }
+        let knots: Vec<F> = params_into(knots)?;
+        let (xs, ys, tangents) = (&knots[..len], &knots[len..2 * len], &knots[2 * len..]);
+        // The prediction is monotonic only for increasing keys, non decreasing targets and non
+        // negative tangents
+        if knots.iter().any(|k| !k.is_finite())
+            || xs.windows(2).any(|w| w[0] >= w[1])
+            || ys.windows(2).any(|w| w[0] > w[1])
+            || tangents.iter().any(|&t| t < F::zero())
+        {
+            return None;
+        }
+        Some(SplineModel {
+            n_knots,
+            xs: xs.to_vec(),
+            ys: ys.to_vec(),
+            tangents: tangents.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::LinearModel, test_utilities::clustered_data};
+
+    #[test]
+    fn interpolates_knots() {
+        let xs: Vec<f64> = vec![1., 2., 3., 10., 11., 12.];
+        let ys: Vec<f64> = vec![0., 1., 2., 3., 4., 5.];
+        let mut model = SplineModel::with_knots(10);
+        model.fit(&xs, &ys).unwrap();
+        assert_eq!(6, model.knots().len());
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            assert_delta!(*y, model.predict(*x), 1e-9);
+        }
+    }
+
+    #[test]
+    fn choose_knots_evenly() {
+        let xs: Vec<f64> = (0..9).map(|i| i as f64).collect();
+        let ys: Vec<f64> = xs.iter().map(|x| x * 2.).collect();
+        let mut model = SplineModel::with_knots(5);
+        model.fit(&xs, &ys).unwrap();
+        assert_eq!(
+            vec![(0., 0.), (2., 4.), (4., 8.), (6., 12.), (8., 16.)],
+            model.knots()
+        );
+    }
+
+    #[test]
+    fn extrapolate_with_end_tangents() {
+        let xs: Vec<f64> = vec![0., 1., 2., 3.];
+        let ys: Vec<f64> = vec![0., 2., 4., 6.];
+        let mut model = SplineModel::with_knots(4);
+        model.fit(&xs, &ys).unwrap();
+        assert_eq!(&[2., 2., 2., 2.], model.tangents());
+        assert_delta!(1f64, model.predict(0.5), 1e-9);
+        assert_delta!(-2f64, model.predict(-1.), 1e-9);
+        assert_delta!(10f64, model.predict(5.), 1e-9);
+    }
+
+    #[test]
+    fn limit_tangents() {
+        // Slopes of the knot intervals are 0, 1, 9 and 0
+        let xs: Vec<f64> = vec![0., 1., 2., 3., 4.];
+        let ys: Vec<f64> = vec![0., 0., 1., 10., 10.];
+        let mut model = SplineModel::with_knots(5);
+        model.fit(&xs, &ys).unwrap();
+        // Flat intervals zero both of their tangents, and the tangent of the knot between the
+        // slopes 1 and 9 is scaled down from their mean 5 so that alpha^2 + beta^2 = 9
+        assert_eq!(&[0., 0., 3., 0., 0.], model.tangents());
+        assert_eq!(0., model.predict(0.5));
+        assert_eq!(10., model.predict(3.5));
+        let mut last = 0.;
+        let mut x = 1.;
+        while x < 3. {
+            let y = model.predict(x);
+            assert!(last <= y && y <= 10.);
+            last = y;
+            x += 0.01;
+        }
+    }
+
+    #[test]
+    fn tangents_within_monotone_region() {
+        let (xs, ys) = clustered_data();
+        let mut model = SplineModel::with_knots(64);
+        model.fit(&xs, &ys).unwrap();
+        let knots = model.knots();
+        let tangents = model.tangents();
+        assert!(tangents.iter().all(|t| *t >= 0.));
+        for k in 0..knots.len() - 1 {
+            let delta = (knots[k + 1].1 - knots[k].1) / (knots[k + 1].0 - knots[k].0);
+            let alpha = tangents[k] / delta;
+            let beta = tangents[k + 1] / delta;
+            assert!(alpha * alpha + beta * beta <= 9. + 1e-9);
+        }
+    }
+
+    #[test]
+    fn more_accurate_than_linear() {
+        let (xs, ys) = clustered_data();
+        let mut linear = LinearModel::new();
+        linear.fit(&xs, &ys).unwrap();
+        let mut spline = SplineModel::with_knots(64);
+        spline.fit(&xs, &ys).unwrap();
+        assert_eq!(64, spline.knots().len());
+        assert!(spline.evaluate(&xs, &ys) < linear.evaluate(&xs, &ys));
+    }
+
+    #[test]
+    fn monotonic_predict() {
+        let (xs, ys) = clustered_data();
+        for n_knots in [2, 7, 100] {
+            let mut model = SplineModel::with_knots(n_knots);
+            model.fit(&xs, &ys).unwrap();
+            let mut last = f64::NEG_INFINITY;
+            let mut x = -10.;
+            while x < 7000. {
+                let y = model.predict(x);
+                assert!(y >= last);
+                last = y;
+                x += 0.37;
+            }
+        }
+    }
+
+    #[test]
+    fn unpredict() {
+        let (xs, ys) = clustered_data();
+        let mut model = SplineModel::with_knots(16);
+        model.fit(&xs, &ys).unwrap();
+        for (x, y) in model.knots() {
+            assert!(model.unpredict(y) <= x);
+        }
+        for y in ys.iter() {
+            let x = model.unpredict(*y);
+            for key in xs.iter() {
+                if *key < x - 1e-9 {
+                    assert!(model.predict(*key) < *y);
+                } else if *key >= x {
+                    assert!(model.predict(*key) >= *y - 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let xs: Vec<f64> = vec![1., 1., 1., 2., 3., 3.];
+        let ys: Vec<f64> = vec![0., 1., 2., 3., 4., 5.];
+        let mut model = SplineModel::new();
+        model.fit(&xs, &ys).unwrap();
+        assert_eq!(vec![(1., 0.), (2., 3.), (3., 4.)], model.knots());
+        assert!(model.predict(1.5) <= model.predict(2.5));
+    }
+
+    #[test]
+    fn should_fail_for_empty_vecs() {
+        let mut model = SplineModel::<f64>::new();
+        assert_eq!(Err(Error::EmptyVal), model.fit(&[], &[]));
+    }
+
+    #[test]
+    fn should_fail_for_non_finite_keys() {
+        let mut model = SplineModel::<f64>::new();
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            model.fit_tuple(&[(1., 0.), (f64::NAN, 1.)])
+        );
+        assert!(model.knots().is_empty());
+    }
+}
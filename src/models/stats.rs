@@ -1,5 +1,37 @@
 use core::iter::Sum;
 use num_traits::{cast::FromPrimitive, float::Float};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Number of values summed in order by one chunk. The sums of the chunks are added in order too,
+/// so the parallel and sequential sums are identical.
+const SUM_CHUNK: usize = 1 << 16;
+
+/// Sums the function of every value, chunk by chunk
+fn chunked_sum<F, G>(values: &[F], f: G) -> F
+where
+    F: Float,
+    G: Fn(F) -> F,
+{
+    values
+        .chunks(SUM_CHUNK)
+        .map(|chunk| chunk.iter().fold(F::zero(), |sum, &x| sum + f(x)))
+        .fold(F::zero(), |sum, x| sum + x)
+}
+
+/// Sums the function of every value, with the chunks summed in parallel
+#[cfg(feature = "rayon")]
+fn par_chunked_sum<F, G>(values: &[F], f: G) -> F
+where
+    F: Float + Send + Sync,
+    G: Fn(F) -> F + Sync,
+{
+    let sums: Vec<F> = values
+        .par_chunks(SUM_CHUNK)
+        .map(|chunk| chunk.iter().fold(F::zero(), |sum, &x| sum + f(x)))
+        .collect();
+    sums.into_iter().fold(F::zero(), |sum, x| sum + x)
+}
 
 /// Calculates mean of a slice of values
 pub fn mean<F>(values: &[F]) -> F
@@ -9,8 +41,19 @@ where
     if values.is_empty() {
         return F::zero();
     }
-    let sum: F = values.iter().cloned().map(Into::into).sum();
-    sum / F::from(values.len()).unwrap()
+    chunked_sum(values, |x| x) / F::from(values.len()).unwrap()
+}
+
+/// Calculates mean of a slice of values in parallel, the result is identical to `mean`
+#[cfg(feature = "rayon")]
+pub fn par_mean<F>(values: &[F]) -> F
+where
+    F: Float + Send + Sync,
+{
+    if values.is_empty() {
+        return F::zero();
+    }
+    par_chunked_sum(values, |x| x) / F::from(values.len()).unwrap()
 }
 
 /// Calculates variance of a slice of values
@@ -22,12 +65,23 @@ where
         return F::zero();
     }
     let mean = mean(values);
+    let two = F::from(2.0).unwrap();
+    let diff_sum = chunked_sum(values, |x| (x - mean).powf(two));
+    diff_sum / F::from(values.len()).unwrap()
+}
 
-    let diff_sum: F = values
-        .iter()
-        .cloned()
-        .map(|x| (x - mean).powf(F::from(2.0).unwrap()))
-        .sum();
+/// Calculates variance of a slice of values in parallel, the result is identical to `variance`
+#[cfg(feature = "rayon")]
+pub fn par_variance<F>(values: &[F]) -> F
+where
+    F: Float + Send + Sync,
+{
+    if values.is_empty() {
+        return F::zero();
+    }
+    let mean = par_mean(values);
+    let two = F::from(2.0).unwrap();
+    let diff_sum = par_chunked_sum(values, |x| (x - mean).powf(two));
     diff_sum / F::from(values.len()).unwrap()
 }
 
@@ -133,6 +187,17 @@ mod tests {
         assert_delta!(2., variance(&values), 0.00001);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_mean_variance() {
+        let values: Vec<f64> = (0..200_000)
+            .map(|i| ((i * 7919) % 1000) as f64 / 3.)
+            .collect();
+        assert_eq!(mean(&values), par_mean(&values));
+        assert_eq!(variance(&values), par_variance(&values));
+        assert_eq!(0., par_variance::<f64>(&[]));
+    }
+
     #[test]
     fn covariance_empty_vec() {
         let x_values: Vec<f64> = vec![];
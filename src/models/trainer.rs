@@ -1,8 +1,11 @@
+#[cfg(feature = "rayon")]
+use crate::models::{par_batch_predict, par_variance};
 use crate::{
     error::Error,
-    geometry::{helper::*, Axis, Point},
-    models::{variance, Model},
+    geometry::{helper::*, Axis, Point, PointLike},
+    models::{variance, AutoModel, Model, ModelScore},
 };
+use core::fmt::Debug;
 use core::iter::Sum;
 use num_traits::{cast::FromPrimitive, Float};
 
@@ -12,7 +15,7 @@ use num_traits::{cast::FromPrimitive, Float};
 pub struct Trainer<F> {
     train_x: Vec<F>,
     train_y: Vec<F>,
-    axis: Axis,
+    axis: usize,
 }
 
 impl<F> Default for Trainer<F> {
@@ -20,7 +23,7 @@ impl<F> Default for Trainer<F> {
         Self {
             train_x: Vec::<F>::new(),
             train_y: Vec::<F>::new(),
-            axis: Axis::X,
+            axis: Axis::X.index(),
         }
     }
 }
@@ -41,8 +44,9 @@ where
         &self.train_y
     }
 
-    pub fn axis(&self) -> &Axis {
-        &self.axis
+    /// Returns the index of the axis that the training data is taken along
+    pub fn axis(&self) -> usize {
+        self.axis
     }
 
     pub fn set_train_x(&mut self, xs: Vec<F>) {
@@ -53,19 +57,37 @@ where
         self.train_y = ys
     }
 
-    pub fn set_axis(&mut self, axis: Axis) {
+    pub fn set_axis(&mut self, axis: usize) {
         self.axis = axis
     }
 
     /// Training with provided model
     ///
-    /// Returns trained/fitted model on success, otherwise returns an error
+    /// Returns trained/fitted model on success, otherwise returns an error, including
+    /// Error::NotMonotonic if the predictions of the fitted model decrease along the sorted keys
     pub fn train<'a, M: Model<F = F> + 'a>(&self, model: &'a mut M) -> Result<(), Error> {
         model.fit(&self.train_x, &self.train_y)?;
+        let predictions = model.batch_predict(&self.train_x);
+        if !predictions.windows(2).all(|w| w[0] <= w[1]) {
+            return Err(Error::NotMonotonic);
+        }
         Ok(())
     }
 }
 
+impl<F> Trainer<F>
+where
+    F: Float + Sum + FromPrimitive + Debug,
+{
+    /// Selects the most accurate of the candidate models
+    ///
+    /// Returns the selected model fitted on the training data and the validation scores of every
+    /// candidate on success, otherwise returns an error
+    pub fn select_best(&self) -> Result<(AutoModel<F>, Vec<ModelScore<F>>), Error> {
+        AutoModel::select(&self.train_x, &self.train_y)
+    }
+}
+
 impl<F> Trainer<F>
 where
     F: Float + Sum + FromPrimitive,
@@ -92,50 +114,190 @@ where
         let mut ps: Vec<Point<F>> = xs
             .iter()
             .zip(ys.iter())
-            .map(|(&x, &y)| Point { x, y })
+            .map(|(&x, &y)| Point::new(x, y))
             .collect();
+        *self = Self::with_points(&mut ps)?;
+        Ok(ps)
+    }
 
-        // set train_x to data with larger variance
-        if variance(&xs) > variance(&ys) {
-            // sort along x
-            sort_by_x(&mut ps);
-            self.set_axis(Axis::X);
-            self.set_train_x(extract_x(&ps));
-        } else {
-            // sort along y
-            sort_by_y(&mut ps);
-            self.set_axis(Axis::Y);
-            self.set_train_x(extract_y(&ps));
-        };
+    /// Preprocess with a slice of points that satisfy Trainer's requirements, the points are
+    /// sorted along the axis with the largest variance among the D dimensions
+    ///
+    /// Returns prepared Trainer Ok((Trainer)) on success, otherwise returns an error
+    pub fn with_points<P, const D: usize>(ps: &mut [P]) -> Result<Self, Error>
+    where
+        P: PointLike<F, D>,
+    {
+        assert_empty!(ps);
+        check_points(ps)?;
 
+        // set train_x to data with largest variance, ties go to the later axis
+        let mut axis = 0;
+        let mut max_variance = F::zero();
+        for i in 0..D {
+            let axis_variance = variance(&extract_axis(ps, i));
+            if i == 0 || axis_variance >= max_variance {
+                axis = i;
+                max_variance = axis_variance;
+            }
+        }
+        if max_variance <= F::zero() {
+            return Err(Error::DegenerateAxis);
+        }
+        Ok(Self::sorted_along(ps, axis))
+    }
+
+    /// Preprocess with a slice of points that satisfy Trainer's requirements, the points are
+    /// sorted along the given axis
+    ///
+    /// Returns prepared Trainer Ok((Trainer)) on success, otherwise returns an error, which is
+    /// `Error::InvalidConfig` if `axis` is not less than D
+    pub fn with_points_along<P, const D: usize>(ps: &mut [P], axis: usize) -> Result<Self, Error>
+    where
+        P: PointLike<F, D>,
+    {
+        if axis >= D {
+            return Err(Error::InvalidConfig);
+        }
+        assert_empty!(ps);
+        check_points(ps)?;
+        if variance(&extract_axis(ps, axis)) <= F::zero() {
+            return Err(Error::DegenerateAxis);
+        }
+        Ok(Self::sorted_along(ps, axis))
+    }
+
+    /// Sorts the points along the axis, and takes their ranks as training targets
+    fn sorted_along<P, const D: usize>(ps: &mut [P], axis: usize) -> Self
+    where
+        P: PointLike<F, D>,
+    {
+        sort_by_axis(ps, axis);
+        let train_x = extract_axis(ps, axis);
         let train_y: Vec<F> = (0..ps.len()).map(|id| F::from_usize(id).unwrap()).collect();
-        self.set_train_y(train_y);
-        Ok(ps)
+        Self {
+            train_x,
+            train_y,
+            axis,
+        }
     }
+}
 
-    /// Preprocess with Vec<Point<F>> that satisfy Trainer's requirements
+#[cfg(feature = "rayon")]
+impl<F> Trainer<F>
+where
+    F: Float + Sum + FromPrimitive + Send + Sync,
+{
+    /// Parallel version of `with_points`, the points are checked, sorted and their variances
+    /// computed in parallel. The trainer and the order of the points are identical to
+    /// `with_points`.
     ///
     /// Returns prepared Trainer Ok((Trainer)) on success, otherwise returns an error
-    pub fn with_points(ps: &mut [Point<F>]) -> Result<Self, Error> {
-        let px: Vec<F> = extract_x(ps);
-        let py: Vec<F> = extract_y(ps);
-        assert_eq_len!(px, py);
-        let x_variance = variance(&px);
-        let y_variance = variance(&py);
-        // set train_x to data with larger variance
-        let (axis, train_x) = if x_variance > y_variance {
-            sort_by_x(ps);
-            (Axis::X, extract_x(ps))
-        } else {
-            sort_by_y(ps);
-            (Axis::Y, extract_y(ps))
-        };
-        let train_y: Vec<F> = (0..ps.len()).map(|id| F::from_usize(id).unwrap()).collect();
-        Ok(Self {
+    pub fn par_with_points<P, const D: usize>(ps: &mut [P]) -> Result<Self, Error>
+    where
+        P: PointLike<F, D> + Send + Sync,
+    {
+        assert_empty!(ps);
+        par_check_points(ps)?;
+
+        // set train_x to data with largest variance, ties go to the later axis
+        let mut axis = 0;
+        let mut max_variance = F::zero();
+        for i in 0..D {
+            let axis_variance = par_variance(&par_extract_axis(ps, i));
+            if i == 0 || axis_variance >= max_variance {
+                axis = i;
+                max_variance = axis_variance;
+            }
+        }
+        if max_variance <= F::zero() {
+            return Err(Error::DegenerateAxis);
+        }
+        Ok(Self::par_sorted_along(ps, axis))
+    }
+
+    /// Parallel version of `with_points_along`, identical to it
+    pub fn par_with_points_along<P, const D: usize>(
+        ps: &mut [P],
+        axis: usize,
+    ) -> Result<Self, Error>
+    where
+        P: PointLike<F, D> + Send + Sync,
+    {
+        if axis >= D {
+            return Err(Error::InvalidConfig);
+        }
+        assert_empty!(ps);
+        par_check_points(ps)?;
+        if par_variance(&par_extract_axis(ps, axis)) <= F::zero() {
+            return Err(Error::DegenerateAxis);
+        }
+        Ok(Self::par_sorted_along(ps, axis))
+    }
+
+    /// Sorts the points along the axis in parallel, and takes their ranks as training targets
+    fn par_sorted_along<P, const D: usize>(ps: &mut [P], axis: usize) -> Self
+    where
+        P: PointLike<F, D> + Send + Sync,
+    {
+        use rayon::prelude::*;
+        par_sort_by_axis(ps, axis);
+        let train_x = par_extract_axis(ps, axis);
+        let train_y: Vec<F> = (0..ps.len())
+            .into_par_iter()
+            .map(|id| F::from_usize(id).unwrap())
+            .collect();
+        Self {
             train_x,
             train_y,
             axis,
-        })
+        }
+    }
+
+    /// Parallel version of `train`, the monotonicity of the model is checked with parallel
+    /// predictions
+    pub fn par_train<'a, M: Model<F = F> + Sync + 'a>(
+        &self,
+        model: &'a mut M,
+    ) -> Result<(), Error> {
+        use rayon::prelude::*;
+        model.fit(&self.train_x, &self.train_y)?;
+        let predictions = par_batch_predict(&*model, &self.train_x);
+        if !predictions.par_windows(2).all(|w| w[0] <= w[1]) {
+            return Err(Error::NotMonotonic);
+        }
+        Ok(())
+    }
+}
+
+/// Checks that every coordinate of the points is finite
+fn check_points<F, P, const D: usize>(ps: &[P]) -> Result<(), Error>
+where
+    F: Float,
+    P: PointLike<F, D>,
+{
+    if ps.iter().all(|p| p.coords().iter().all(|c| c.is_finite())) {
+        Ok(())
+    } else {
+        Err(Error::NonFiniteCoordinate)
+    }
+}
+
+/// Checks that every coordinate of the points is finite in parallel
+#[cfg(feature = "rayon")]
+fn par_check_points<F, P, const D: usize>(ps: &[P]) -> Result<(), Error>
+where
+    F: Float,
+    P: PointLike<F, D> + Sync,
+{
+    use rayon::prelude::*;
+    if ps
+        .par_iter()
+        .all(|p| p.coords().iter().all(|c| c.is_finite()))
+    {
+        Ok(())
+    } else {
+        Err(Error::NonFiniteCoordinate)
     }
 }
 
@@ -146,18 +308,18 @@ mod tests {
     #[test]
     fn sort_by() {
         let mut data: Vec<Point<f64>> = vec![
-            Point { x: 1., y: 1. },
-            Point { x: 3., y: 1. },
-            Point { x: 2., y: 1. },
-            Point { x: 3., y: 2. },
-            Point { x: 5., y: 1. },
+            Point::new(1., 1.),
+            Point::new(3., 1.),
+            Point::new(2., 1.),
+            Point::new(3., 2.),
+            Point::new(5., 1.),
         ];
         let data_sort_by_x: Vec<Point<f64>> = vec![
-            Point { x: 1., y: 1. },
-            Point { x: 2., y: 1. },
-            Point { x: 3., y: 1. },
-            Point { x: 3., y: 2. },
-            Point { x: 5., y: 1. },
+            Point::new(1., 1.),
+            Point::new(2., 1.),
+            Point::new(3., 1.),
+            Point::new(3., 2.),
+            Point::new(5., 1.),
         ];
         sort_by_x(&mut data);
 
@@ -167,11 +329,11 @@ mod tests {
     #[test]
     fn train() {
         let mut data: Vec<Point<f64>> = vec![
-            Point { x: 1., y: 1. },
-            Point { x: 3., y: 1. },
-            Point { x: 2., y: 1. },
-            Point { x: 3., y: 2. },
-            Point { x: 5., y: 1. },
+            Point::new(1., 1.),
+            Point::new(3., 1.),
+            Point::new(2., 1.),
+            Point::new(3., 2.),
+            Point::new(5., 1.),
         ];
         let trainer = Trainer::with_points(&mut data).unwrap();
         let test_x = vec![1., 2., 3., 3., 5.];
@@ -180,4 +342,113 @@ mod tests {
         assert_eq!(&test_x, trainer.train_x());
         assert_eq!(&test_y, trainer.train_y());
     }
+
+    #[test]
+    fn train_along_largest_variance_axis() {
+        let mut data: Vec<Point<f64, 3>> = (0..10)
+            .map(|i| Point::from_coords([1., i as f64, (10 - i * i) as f64]))
+            .collect();
+        let trainer = Trainer::with_points(&mut data).unwrap();
+
+        assert_eq!(2, trainer.axis());
+        assert_eq!(Point::from_coords([1., 9., -71.]), data[0]);
+        assert_eq!(&extract_axis(&data, 2), trainer.train_x());
+    }
+
+    #[test]
+    fn train_along_given_axis() {
+        let mut data: Vec<Point<f64, 3>> = (0..10)
+            .map(|i| Point::from_coords([1., i as f64, (10 - i * i) as f64]))
+            .collect();
+        let trainer = Trainer::with_points_along(&mut data, 1).unwrap();
+
+        assert_eq!(1, trainer.axis());
+        assert_eq!(Point::from_coords([1., 0., 10.]), data[0]);
+        assert_eq!(&extract_axis(&data, 1), trainer.train_x());
+        assert_eq!(
+            Err(Error::DegenerateAxis),
+            Trainer::with_points_along(&mut data, 0).map(|t| t.axis())
+        );
+        assert_eq!(
+            Err(Error::InvalidConfig),
+            Trainer::with_points_along(&mut data, 3).map(|t| t.axis())
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_train_along_given_axis() {
+        let mut data: Vec<Point<f64, 3>> = (0..10)
+            .map(|i| Point::from_coords([1., i as f64, (10 - i * i) as f64]))
+            .collect();
+        let trainer = Trainer::par_with_points_along(&mut data, 1).unwrap();
+
+        assert_eq!(1, trainer.axis());
+        assert_eq!(Point::from_coords([1., 0., 10.]), data[0]);
+        assert_eq!(
+            Err(Error::InvalidConfig),
+            Trainer::par_with_points_along(&mut data, 3).map(|t| t.axis())
+        );
+    }
+
+    #[test]
+    fn train_with_point_like() {
+        let mut data = vec![[1., 5.], [2., 1.], [3., 3.]];
+        let trainer = Trainer::with_points(&mut data).unwrap();
+
+        assert_eq!(1, trainer.axis());
+        assert_eq!(vec![[2., 1.], [3., 3.], [1., 5.]], data);
+    }
+
+    #[test]
+    fn should_fail_for_invalid_points() {
+        let mut empty: Vec<Point<f64>> = vec![];
+        let mut nan = vec![Point::new(1., 1.), Point::new(f64::NAN, 2.)];
+        let mut same = vec![Point::new(1., 1.), Point::new(1., 1.)];
+
+        assert_eq!(
+            Err(Error::EmptyVal),
+            Trainer::with_points(&mut empty).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            Trainer::with_points(&mut nan).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::DegenerateAxis),
+            Trainer::with_points(&mut same).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::NonFiniteCoordinate),
+            Trainer::with_data(vec![1., f64::INFINITY], vec![1., 2.]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn should_fail_for_non_monotonic_model() {
+        use crate::models::LinearModel;
+
+        let mut trainer = Trainer::new();
+        trainer.set_train_x(vec![1., 2., 3.]);
+        trainer.set_train_y(vec![2., 1., 0.]);
+        let mut model = LinearModel::new();
+        assert_eq!(Err(Error::NotMonotonic), trainer.train(&mut model));
+    }
+
+    #[test]
+    fn select_best() {
+        let mut data: Vec<Point<f64>> = (0..200)
+            .map(|i| Point::new((i as f64 / 10.).exp(), 1.))
+            .collect();
+        let trainer = Trainer::with_points(&mut data).unwrap();
+        let (model, scores) = trainer.select_best().unwrap();
+
+        assert_eq!(4, scores.len());
+        let best = scores
+            .iter()
+            .min_by(|a, b| a.rmse.partial_cmp(&b.rmse).unwrap())
+            .unwrap();
+        assert_eq!(best.name, model.name());
+        assert_ne!("linear", model.name());
+    }
 }
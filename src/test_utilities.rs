@@ -21,6 +21,36 @@ pub fn create_random_point_type_points(num_points: usize, seed: &[u8; 32]) -> Ve
     // result.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
     result
         .into_iter()
-        .map(|(x, y)| Point { x, y })
+        .map(|(x, y)| Point::new(x, y))
         .collect::<Vec<_>>()
 }
+
+pub fn create_random_nd_points<const D: usize>(
+    num_points: usize,
+    seed: &[u8; 32],
+) -> Vec<Point<f64, D>> {
+    let mut rng = SmallRng::from_seed(*seed);
+    (0..num_points)
+        .map(|_| Point::from_coords(core::array::from_fn(|_| rng.random())))
+        .collect()
+}
+
+/// Returns sorted keys in three clusters, two linear and a quadratic one, which a single
+/// linear model fits badly
+pub fn clustered_keys() -> Vec<f64> {
+    let mut xs: Vec<f64> = Vec::new();
+    for i in 0..300 {
+        xs.push(i as f64 * 0.001);
+        xs.push(10. + (i as f64).powi(2) * 0.01);
+        xs.push(5000. + i as f64 * 3.);
+    }
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs
+}
+
+/// Returns the clustered keys and their ranks
+pub fn clustered_data() -> (Vec<f64>, Vec<f64>) {
+    let xs = clustered_keys();
+    let ys = (0..xs.len()).map(|i| i as f64).collect();
+    (xs, ys)
+}